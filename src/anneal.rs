@@ -0,0 +1,166 @@
+//! Simulated annealing on the order of an arranged slideshow.
+
+use crate::improve::{Move, Slideshow, MAX_SEGMENT_LENGTH};
+use crate::slides::reorder_slides;
+use crate::tags::TagSets;
+use crate::{rate_slideshow, vocabulary_size, Budget, Rng, Slide, TagSet};
+
+//How many positions apart the two ends of a move can be
+const NEIGHBOURHOOD_SIZE: usize = 200;
+//Iterations between checks of the budget
+const BUDGET_CHECK_INTERVAL: u64 = 1024;
+
+/// How the temperature goes from its start to its end value over the iterations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cooling {
+    Linear,
+    Exponential,
+}
+
+/// Relative frequencies of the kinds of moves tried.
+#[derive(Debug, Clone, Copy)]
+pub struct MoveMix {
+    /// Exchange two slides
+    pub swap: u32,
+    /// Reverse the slides between two positions
+    pub reverse: u32,
+    /// Move a run of up to three slides elsewhere
+    pub relocate: u32,
+}
+
+/// Parameters of an annealing run.
+#[derive(Debug, Clone, Copy)]
+pub struct AnnealingSchedule {
+    pub iterations: u64,
+    /// Temperature at the first iteration, in points of score
+    pub start_temperature: f64,
+    /// Temperature at the last iteration, in points of score
+    pub end_temperature: f64,
+    pub cooling: Cooling,
+    pub move_mix: MoveMix,
+}
+
+impl Default for AnnealingSchedule {
+    fn default() -> Self {
+        AnnealingSchedule {
+            iterations: 10_000_000,
+            start_temperature: 0.5,
+            end_temperature: 0.01,
+            cooling: Cooling::Exponential,
+            move_mix: MoveMix {
+                swap: 1,
+                reverse: 1,
+                relocate: 1,
+            },
+        }
+    }
+}
+
+impl AnnealingSchedule {
+    fn temperature(&self, progress: f64) -> f64 {
+        match self.cooling {
+            Cooling::Linear => {
+                self.start_temperature + (self.end_temperature - self.start_temperature) * progress
+            }
+            Cooling::Exponential => {
+                self.start_temperature
+                    * (self.end_temperature / self.start_temperature).powf(progress)
+            }
+        }
+    }
+
+    fn random_move(&self, rng: &mut Rng) -> Move {
+        let MoveMix {
+            swap,
+            reverse,
+            relocate,
+        } = self.move_mix;
+        let choice = rng.below((swap + reverse + relocate) as usize) as u32;
+        if choice < swap {
+            Move::Swap
+        } else if choice < swap + reverse {
+            Move::Reverse
+        } else if rng.below(2) == 0 {
+            Move::Forward(1 + rng.below(MAX_SEGMENT_LENGTH))
+        } else {
+            Move::Backward(1 + rng.below(MAX_SEGMENT_LENGTH))
+        }
+    }
+}
+
+/// Improve the order of the slides with simulated annealing, returning the best order found.
+///
+/// Moves between nearby positions are drawn at random and scored from the transitions they
+/// change. Improvements are always accepted, and a move losing `delta` points with probability
+/// `exp(-delta / temperature)`. The run stops early when the budget is exhausted, and its gain
+/// is reported under `name`.
+pub fn anneal_slideshow(
+    slides: Vec<Slide>,
+    name: &str,
+    schedule: &AnnealingSchedule,
+    rng: &mut Rng,
+    budget: &Budget,
+) -> Vec<Slide> {
+    if slides.len() < 2 {
+        return slides;
+    }
+    let initial_score = rate_slideshow(&slides);
+    let (order, best_score) = match TagSets::new(&slides, vocabulary_size(&slides)) {
+        TagSets::SortedLists(tag_sets) => {
+            anneal_order(&tag_sets, initial_score, schedule, rng, budget)
+        }
+        TagSets::Bitsets(tag_sets) => anneal_order(&tag_sets, initial_score, schedule, rng, budget),
+    };
+    println!(
+        "Annealing improved {} by {}",
+        name,
+        best_score - i64::from(initial_score)
+    );
+    reorder_slides(slides, order)
+}
+
+//Best order of the slides the tag sets belong to found while annealing, and its score
+fn anneal_order<T: TagSet>(
+    tag_sets: &[T],
+    initial_score: u32,
+    schedule: &AnnealingSchedule,
+    rng: &mut Rng,
+    budget: &Budget,
+) -> (Vec<usize>, i64) {
+    let mut slideshow = Slideshow::new(tag_sets);
+    let mut best_order = slideshow.order.clone();
+    let mut score = i64::from(initial_score);
+    let mut best_score = score;
+    let slide_count = tag_sets.len();
+    let mut temperature = schedule.start_temperature;
+    for iteration in 0..schedule.iterations {
+        if iteration % BUDGET_CHECK_INTERVAL == 0 {
+            if budget.is_exhausted() {
+                break;
+            }
+            temperature = schedule.temperature(iteration as f64 / schedule.iterations as f64);
+        }
+        let first = rng.below(slide_count - 1);
+        let last = first + 1 + rng.below(NEIGHBOURHOOD_SIZE.min(slide_count - 1 - first));
+        let slide_move = schedule.random_move(rng);
+        let delta = match slideshow.delta(first, last, slide_move) {
+            Some(delta) => delta,
+            None => continue,
+        };
+        if delta < 0 && rng.next_f64() >= (delta as f64 / temperature).exp() {
+            continue;
+        }
+        //Leaving the best order found so far, so keep a copy of it
+        if delta < 0 && score > best_score {
+            best_order.copy_from_slice(&slideshow.order);
+            best_score = score;
+        }
+        slideshow.apply(first, last, slide_move);
+        score += delta;
+    }
+    if score > best_score {
+        (slideshow.order, score)
+    } else {
+        (best_order, best_score)
+    }
+}
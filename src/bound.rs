@@ -0,0 +1,106 @@
+//! Upper bounds on the score any slideshow of a set of pictures can reach.
+
+use crate::tags::index_by_tag;
+use crate::{calculate_score, slide_from_pictures, Budget, Orientation, Picture};
+use rayon::prelude::*;
+use std::cmp;
+
+/// Upper bounds on the score of the best slideshow of some pictures.
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreBound {
+    /// A transition scores at most half the tags of either slide. Charging every transition of
+    /// the slideshow to the slide on the side away from the slide with the most tags, each slide
+    /// but that one is charged once, so the score is at most the sum over the slides of half
+    /// their tags, minus that of the slide with the most tags.
+    pub path: u32,
+    /// Every slide takes part in at most two transitions, so the score is at most half the sum
+    /// over the slides of their two best transitions with any other slide. Only computed when
+    /// there are no vertical pictures, since otherwise the slides aren't known.
+    pub degree: Option<u32>,
+}
+
+impl ScoreBound {
+    /// The tightest of the bounds.
+    pub fn value(&self) -> u32 {
+        self.degree
+            .map_or(self.path, |degree| cmp::min(self.path, degree))
+    }
+}
+
+/// Upper bounds on the score any slideshow of the pictures can reach. The degree bound is left
+/// out if the budget runs out while computing it.
+pub fn calculate_score_bound(pictures: &[Picture], budget: &Budget) -> ScoreBound {
+    let half_tag_counts = pictures
+        .iter()
+        .filter(|picture| picture.orientation == Orientation::Horizontal)
+        .map(|picture| picture.tags.len() as u32 / 2);
+    let horizontal_total: u32 = half_tag_counts.clone().sum();
+    let largest = half_tag_counts.max().unwrap_or(0);
+    //However they are paired, a slide of two vertical pictures has at most their combined tags
+    let vertical_tag_count: usize = pictures
+        .iter()
+        .filter(|picture| picture.orientation == Orientation::Vertical)
+        .map(|picture| picture.tags.len())
+        .sum();
+    let path = horizontal_total + (vertical_tag_count / 2) as u32 - largest;
+    let degree = if vertical_tag_count == 0 {
+        degree_bound(pictures, budget)
+    } else {
+        None
+    };
+    ScoreBound { path, degree }
+}
+
+//Half the sum over the slides of their two best transitions, each picture being on its own slide
+fn degree_bound(pictures: &[Picture], budget: &Budget) -> Option<u32> {
+    //Only slides sharing a tag can score together
+    let slides_by_tag = index_by_tag(pictures.iter().map(|picture| picture.tags.as_slice()));
+    let slides: Vec<_> = pictures
+        .iter()
+        .map(|picture| slide_from_pictures(picture, None))
+        .collect();
+    let best_transitions: Option<Vec<u32>> = slides
+        .par_iter()
+        .enumerate()
+        .map_init(
+            || vec![false; slides.len()],
+            |seen, (index, slide)| {
+                if budget.is_exhausted() {
+                    return None;
+                }
+                let tags = &slide.tags;
+                //No transition scores more than half the tags of the slide
+                let most = tags.len() as u32 / 2;
+                let (mut best, mut second_best) = (0, 0);
+                let mut others = Vec::new();
+                seen[index] = true;
+                'tags: for &tag in tags.iter() {
+                    for &other in slides_by_tag[tag as usize].iter() {
+                        let other = other as usize;
+                        if seen[other] {
+                            continue;
+                        }
+                        seen[other] = true;
+                        others.push(other);
+                        let score = calculate_score(tags, &slides[other].tags);
+                        if score > best {
+                            second_best = best;
+                            best = score;
+                        } else if score > second_best {
+                            second_best = score;
+                        }
+                        if second_best == most {
+                            break 'tags;
+                        }
+                    }
+                }
+                seen[index] = false;
+                for other in others {
+                    seen[other] = false;
+                }
+                Some(best + second_best)
+            },
+        )
+        .collect();
+    best_transitions.map(|best_transitions| best_transitions.iter().sum::<u32>() / 2)
+}
@@ -0,0 +1,52 @@
+//! Limits on how long the solver may keep improving a slideshow.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Point at which the solver should stop and return the best slideshow it has, either a deadline
+/// or an interruption requested from outside, e.g. by a signal handler.
+///
+/// Every phase checks the budget between steps, so a run overshoots the limit by at most one
+/// step.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Budget {
+    deadline: Option<Instant>,
+    interrupted: Option<&'static AtomicBool>,
+}
+
+impl Budget {
+    /// A budget that never runs out.
+    pub fn unlimited() -> Self {
+        Budget {
+            deadline: None,
+            interrupted: None,
+        }
+    }
+
+    /// A budget running out `time_limit` from now, or never if that is too far away to represent.
+    pub fn with_time_limit(time_limit: Duration) -> Self {
+        Budget {
+            deadline: Instant::now().checked_add(time_limit),
+            interrupted: None,
+        }
+    }
+
+    /// The same budget, also running out as soon as `interrupted` is set.
+    pub fn interruptible(self, interrupted: &'static AtomicBool) -> Self {
+        Budget {
+            interrupted: Some(interrupted),
+            ..self
+        }
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        let interrupted = self
+            .interrupted
+            .is_some_and(|interrupted| interrupted.load(Ordering::Relaxed));
+        let expired = match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        };
+        interrupted || expired
+    }
+}
@@ -0,0 +1,345 @@
+//! Local search improving the order of an arranged slideshow.
+
+use crate::slides::reorder_slides;
+use crate::tags::TagSets;
+use crate::{calculate_score, merge_tags, vocabulary_size, Budget, Picture, Slide, TagSet};
+
+//How many positions ahead of a slide the moves involving it reach
+const NEIGHBOURHOOD_SIZE: usize = 50;
+//Longest run of slides relocated by a single move
+pub(crate) const MAX_SEGMENT_LENGTH: usize = 3;
+
+//Changes to the order of the slides between two positions, first and last included
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Move {
+    //Reverse the slides between the positions (2-opt)
+    Reverse,
+    //Exchange the slides at the positions
+    Swap,
+    //Move the first slides to after the last position (or-opt)
+    Forward(usize),
+    //Move the last slides to before the first position (or-opt)
+    Backward(usize),
+}
+
+//Order of the slides the tag sets belong to, which moves are applied to
+pub(crate) struct Slideshow<'a, T> {
+    tag_sets: &'a [T],
+    pub(crate) order: Vec<usize>,
+}
+
+impl<'a, T: TagSet> Slideshow<'a, T> {
+    pub(crate) fn new(tag_sets: &'a [T]) -> Self {
+        Slideshow {
+            tag_sets,
+            order: (0..tag_sets.len()).collect(),
+        }
+    }
+
+    fn slide_at(&self, position: Option<usize>) -> Option<usize> {
+        position.and_then(|position| self.order.get(position).cloned())
+    }
+
+    //Score of the transition between two slides, zero at either end of the slideshow
+    fn transition(&self, left: Option<usize>, right: Option<usize>) -> i64 {
+        match (left, right) {
+            (Some(left), Some(right)) => {
+                i64::from(calculate_score(&self.tag_sets[left], &self.tag_sets[right]))
+            }
+            _ => 0,
+        }
+    }
+
+    //Change of the total score the move would make, computed only from the transitions it breaks
+    //and creates
+    pub(crate) fn delta(&self, first: usize, last: usize, slide_move: Move) -> Option<i64> {
+        let at = |position: usize| self.slide_at(Some(position));
+        let before = self.slide_at(first.checked_sub(1));
+        let (first_slide, last_slide, after) = (at(first), at(last), at(last + 1));
+        let (created, broken) = match slide_move {
+            Move::Reverse => (
+                [(before, last_slide), (first_slide, after), (None, None)],
+                [(before, first_slide), (last_slide, after), (None, None)],
+            ),
+            Move::Swap => {
+                if last < first + 2 {
+                    return None;
+                }
+                let (second, second_to_last) = (at(first + 1), at(last - 1));
+                return Some(
+                    self.transition(before, last_slide)
+                        + self.transition(last_slide, second)
+                        + self.transition(second_to_last, first_slide)
+                        + self.transition(first_slide, after)
+                        - self.transition(before, first_slide)
+                        - self.transition(first_slide, second)
+                        - self.transition(second_to_last, last_slide)
+                        - self.transition(last_slide, after),
+                );
+            }
+            Move::Forward(length) => {
+                if first + length > last {
+                    return None;
+                }
+                let (segment_end, segment_next) = (at(first + length - 1), at(first + length));
+                (
+                    [
+                        (before, segment_next),
+                        (last_slide, first_slide),
+                        (segment_end, after),
+                    ],
+                    [
+                        (before, first_slide),
+                        (segment_end, segment_next),
+                        (last_slide, after),
+                    ],
+                )
+            }
+            Move::Backward(length) => {
+                if last < first + length {
+                    return None;
+                }
+                let (segment_start, segment_previous) = (at(last + 1 - length), at(last - length));
+                (
+                    [
+                        (before, segment_start),
+                        (last_slide, first_slide),
+                        (segment_previous, after),
+                    ],
+                    [
+                        (before, first_slide),
+                        (segment_previous, segment_start),
+                        (last_slide, after),
+                    ],
+                )
+            }
+        };
+        let total = |transitions: [(Option<usize>, Option<usize>); 3]| -> i64 {
+            transitions
+                .iter()
+                .map(|&(left, right)| self.transition(left, right))
+                .sum()
+        };
+        Some(total(created) - total(broken))
+    }
+
+    pub(crate) fn apply(&mut self, first: usize, last: usize, slide_move: Move) {
+        let slides = &mut self.order[first..=last];
+        match slide_move {
+            Move::Reverse => slides.reverse(),
+            Move::Swap => slides.swap(0, last - first),
+            Move::Forward(length) => slides.rotate_left(length),
+            Move::Backward(length) => slides.rotate_right(length),
+        }
+    }
+}
+
+/// Improve the order of the slides with swaps, reversals (2-opt) and relocations of short runs
+/// of slides (or-opt) between nearby positions.
+///
+/// Vertical pictures are also exchanged between nearby slides to improve their pairing. Moves are
+/// scored from the transitions they change only, and the first improving one is applied until
+/// none remains or the budget is exhausted. The gain is reported under `name`.
+pub fn improve_slideshow(
+    mut slides: Vec<Slide>,
+    pictures: &[Picture],
+    name: &str,
+    budget: &Budget,
+) -> Vec<Slide> {
+    let (mut order_gain, mut pairing_gain) = (0, 0);
+    //Better pairs can open up better orders and the other way round, so alternate until neither
+    //finds anything
+    loop {
+        let (reordered_slides, gain) = improve_slide_order(slides, budget);
+        slides = reordered_slides;
+        order_gain += gain;
+        let gain = improve_pairing(&mut slides, pictures, budget);
+        pairing_gain += gain;
+        if gain == 0 || budget.is_exhausted() {
+            break;
+        }
+    }
+    println!(
+        "Local search improved {} by {} ({} from pairing vertical pictures differently)",
+        name,
+        order_gain + pairing_gain,
+        pairing_gain
+    );
+    slides
+}
+
+fn improve_slide_order(slides: Vec<Slide>, budget: &Budget) -> (Vec<Slide>, i64) {
+    let (order, gain) = match TagSets::new(&slides, vocabulary_size(&slides)) {
+        TagSets::SortedLists(tag_sets) => improve_order(&tag_sets, budget),
+        TagSets::Bitsets(tag_sets) => improve_order(&tag_sets, budget),
+    };
+    (reorder_slides(slides, order), gain)
+}
+
+//Exchange vertical pictures between nearby vertical slides when the two new pairs score better
+//with their neighbours, returning the gain
+fn improve_pairing(slides: &mut [Slide], pictures: &[Picture], budget: &Budget) -> i64 {
+    let tags_of = |id: u32| &pictures[id as usize].tags;
+    let mut gain = 0;
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for first in 0..slides.len() {
+            if budget.is_exhausted() {
+                return gain;
+            }
+            let (first_kept, first_given) = match slides[first].second_picture_id {
+                Some(second_picture_id) => (slides[first].picture_id, second_picture_id),
+                None => continue,
+            };
+            let neighbourhood_end = (first + NEIGHBOURHOOD_SIZE).min(slides.len() - 1);
+            for last in first + 1..=neighbourhood_end {
+                let last_pair = match slides[last].second_picture_id {
+                    Some(second_picture_id) => (slides[last].picture_id, second_picture_id),
+                    None => continue,
+                };
+                //Give away the second picture of the first slide for either picture of the last
+                for &(last_kept, last_given) in [last_pair, (last_pair.1, last_pair.0)].iter() {
+                    let first_tags = merge_tags(tags_of(first_kept), tags_of(last_given));
+                    let last_tags = merge_tags(tags_of(last_kept), tags_of(first_given));
+                    let delta = pairing_delta(slides, first, last, &first_tags, &last_tags);
+                    if delta > 0 {
+                        slides[first].second_picture_id = Some(last_given);
+                        slides[first].tags = first_tags;
+                        slides[last].picture_id = last_kept;
+                        slides[last].second_picture_id = Some(first_given);
+                        slides[last].tags = last_tags;
+                        gain += delta;
+                        improved = true;
+                        break;
+                    }
+                }
+                //The first slide changed, continue with the next one
+                if slides[first].second_picture_id != Some(first_given) {
+                    break;
+                }
+            }
+        }
+    }
+    gain
+}
+
+//Change of the total score from giving the slides at the two positions new tags
+fn pairing_delta(
+    slides: &[Slide],
+    first: usize,
+    last: usize,
+    first_tags: &[u32],
+    last_tags: &[u32],
+) -> i64 {
+    let new_tags = |position: usize| match position {
+        _ if position == first => first_tags,
+        _ if position == last => last_tags,
+        _ => &slides[position].tags,
+    };
+    //Transitions are numbered by the position of the slide on their left
+    let mut transitions = vec![first.wrapping_sub(1), first, last - 1, last];
+    transitions.dedup();
+    transitions
+        .into_iter()
+        .filter(|&left| left < slides.len() - 1)
+        .map(|left| {
+            let new_score = calculate_score(new_tags(left), new_tags(left + 1));
+            let old_score = calculate_score(&slides[left].tags, &slides[left + 1].tags);
+            i64::from(new_score) - i64::from(old_score)
+        })
+        .sum()
+}
+
+//Improved order of the slides the tag sets belong to, and how much it gained
+fn improve_order<T: TagSet>(tag_sets: &[T], budget: &Budget) -> (Vec<usize>, i64) {
+    let mut slideshow = Slideshow::new(tag_sets);
+    let moves: Vec<_> = [Move::Reverse, Move::Swap]
+        .iter()
+        .cloned()
+        .chain((1..=MAX_SEGMENT_LENGTH).map(Move::Forward))
+        .chain((1..=MAX_SEGMENT_LENGTH).map(Move::Backward))
+        .collect();
+    let mut gain = 0;
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for first in 0..tag_sets.len() {
+            if budget.is_exhausted() {
+                return (slideshow.order, gain);
+            }
+            let neighbourhood_end = (first + NEIGHBOURHOOD_SIZE).min(tag_sets.len() - 1);
+            for last in first + 1..=neighbourhood_end {
+                for &slide_move in moves.iter() {
+                    if let Some(delta) = slideshow.delta(first, last, slide_move) {
+                        if delta > 0 {
+                            slideshow.apply(first, last, slide_move);
+                            gain += delta;
+                            improved = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    (slideshow.order, gain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rng;
+
+    fn total_score(slideshow: &Slideshow<Vec<u32>>) -> i64 {
+        slideshow
+            .order
+            .windows(2)
+            .map(|pair| {
+                let (left, right) = (&slideshow.tag_sets[pair[0]], &slideshow.tag_sets[pair[1]]);
+                i64::from(calculate_score(left, right))
+            })
+            .sum()
+    }
+
+    #[test]
+    fn delta_matches_rescoring() {
+        let mut rng = Rng::new(7);
+        let tag_sets: Vec<Vec<u32>> = (0..12)
+            .map(|_| {
+                let mut tags: Vec<_> = (0..1 + rng.below(10))
+                    .map(|_| rng.below(16) as u32)
+                    .collect();
+                tags.sort_unstable();
+                tags.dedup();
+                tags
+            })
+            .collect();
+        let mut slideshow = Slideshow::new(&tag_sets);
+        let mut score = total_score(&slideshow);
+        for _ in 0..5000 {
+            //Small slideshows put most moves against its ends
+            let first = rng.below(tag_sets.len() - 1);
+            let last = first + 1 + rng.below(tag_sets.len() - first - 1);
+            let length = 1 + rng.below(MAX_SEGMENT_LENGTH);
+            let slide_move = match rng.below(4) {
+                0 => Move::Reverse,
+                1 => Move::Swap,
+                2 => Move::Forward(length),
+                _ => Move::Backward(length),
+            };
+            if let Some(delta) = slideshow.delta(first, last, slide_move) {
+                slideshow.apply(first, last, slide_move);
+                let new_score = total_score(&slideshow);
+                assert_eq!(
+                    delta,
+                    new_score - score,
+                    "{:?} between {} and {}",
+                    slide_move,
+                    first,
+                    last
+                );
+                score = new_score;
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Parsing of the problem inputs into pictures.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::io::{self, Read};
+
+/// Orientation of a picture. Vertical pictures have to share a slide with another vertical one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+/// A picture of the input, with its tags mapped to numbers and sorted.
+#[derive(Debug)]
+pub struct Picture {
+    /// Position of the picture in the input, used to refer to it in the output
+    pub id: u32,
+    pub orientation: Orientation,
+    pub tags: Vec<u32>,
+}
+
+/// Reason an input couldn't be parsed, with the 1-based position it was found at.
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    //The first line doesn't hold the number of pictures
+    BadHeader {
+        line: usize,
+    },
+    //The file ends before the number of pictures declared in the header is reached
+    CountMismatch {
+        line: usize,
+        declared: usize,
+        found: usize,
+    },
+    UnknownOrientation {
+        line: usize,
+        column: usize,
+        found: String,
+    },
+    //The tag count is missing or isn't a number
+    BadTagCount {
+        line: usize,
+        column: usize,
+    },
+    TagCountMismatch {
+        line: usize,
+        column: usize,
+        declared: usize,
+        found: usize,
+    },
+    //A picture without any tags
+    EmptyTag {
+        line: usize,
+        column: usize,
+    },
+    //A picture listing the same tag more than once, only reported in strict mode
+    DuplicateTag {
+        line: usize,
+        column: usize,
+        tag: String,
+    },
+    //Non-empty lines after the last declared picture
+    TrailingData {
+        line: usize,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io(error) => write!(f, "couldn't read input: {}", error),
+            ParseError::BadHeader { line } => {
+                write!(f, "line {}: expected the number of pictures", line)
+            }
+            ParseError::CountMismatch {
+                line,
+                declared,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} pictures, found only {}",
+                line, declared, found
+            ),
+            ParseError::UnknownOrientation {
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "line {}, column {}: expected orientation H or V, found \"{}\"",
+                line, column, found
+            ),
+            ParseError::BadTagCount { line, column } => write!(
+                f,
+                "line {}, column {}: expected the number of tags",
+                line, column
+            ),
+            ParseError::TagCountMismatch {
+                line,
+                column,
+                declared,
+                found,
+            } => write!(
+                f,
+                "line {}, column {}: expected {} tags, found {}",
+                line, column, declared, found
+            ),
+            ParseError::EmptyTag { line, column } => {
+                write!(f, "line {}, column {}: picture has no tags", line, column)
+            }
+            ParseError::DuplicateTag { line, column, tag } => write!(
+                f,
+                "line {}, column {}: tag \"{}\" is repeated",
+                line, column, tag
+            ),
+            ParseError::TrailingData { line } => {
+                write!(f, "line {}: unexpected data after the last picture", line)
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError::Io(error)
+    }
+}
+
+//1-based column at which a word taken from the line starts
+fn column(line: &str, word: &str) -> usize {
+    word.as_ptr() as usize - line.as_ptr() as usize + 1
+}
+
+/// Parse an input in the format of the problem statement into its pictures.
+///
+/// In strict mode repeated tags within a picture are an error, otherwise they are dropped.
+pub fn parse_input(mut reader: impl Read, strict: bool) -> Result<Vec<Picture>, ParseError> {
+    let mut file = String::new();
+    reader.read_to_string(&mut file)?;
+    let mut lines = file.lines().zip(1..);
+    let declared_pictures: usize = lines
+        .next()
+        .and_then(|(header, _)| header.trim().parse().ok())
+        .ok_or(ParseError::BadHeader { line: 1 })?;
+    let mut tag_map = HashMap::new();
+    //The header can't be trusted with the allocation, there are at most as many pictures as lines
+    let line_count = file.lines().count();
+    let mut pictures = Vec::with_capacity(cmp::min(declared_pictures, line_count - 1));
+    for (line, line_number) in lines.by_ref().take(declared_pictures) {
+        let mut words = line.split_whitespace();
+        let orientation = match words.next() {
+            Some("H") => Orientation::Horizontal,
+            Some("V") => Orientation::Vertical,
+            found => {
+                return Err(ParseError::UnknownOrientation {
+                    line: line_number,
+                    column: found.map_or(1, |word| column(line, word)),
+                    found: found.unwrap_or_default().to_string(),
+                })
+            }
+        };
+        let tag_count = words.next();
+        let declared_tags: usize = tag_count
+            .and_then(|tag_count| tag_count.parse().ok())
+            .ok_or(ParseError::BadTagCount {
+                line: line_number,
+                column: tag_count.map_or(line.len() + 1, |word| column(line, word)),
+            })?;
+        let tag_count_column = column(line, tag_count.unwrap_or_default());
+        let mut tags: Vec<_> = words
+            .map(|tag| {
+                let numerical_tag = tag_map.len() as u32;
+                *tag_map.entry(tag).or_insert(numerical_tag)
+            })
+            .collect();
+        if tags.is_empty() {
+            return Err(ParseError::EmptyTag {
+                line: line_number,
+                column: line.len() + 1,
+            });
+        }
+        if tags.len() != declared_tags {
+            return Err(ParseError::TagCountMismatch {
+                line: line_number,
+                column: tag_count_column,
+                declared: declared_tags,
+                found: tags.len(),
+            });
+        }
+        //Sort tags to enable faster calculation of common_tags
+        tags.sort_unstable();
+        if strict {
+            if let Some(repeated) = tags.windows(2).find(|pair| pair[0] == pair[1]) {
+                let repeated_tag = repeated[0];
+                let tag = line
+                    .split_whitespace()
+                    .skip(2)
+                    .filter(|tag| tag_map[tag] == repeated_tag)
+                    .nth(1)
+                    .unwrap_or_default();
+                return Err(ParseError::DuplicateTag {
+                    line: line_number,
+                    column: column(line, tag),
+                    tag: tag.to_string(),
+                });
+            }
+        } else {
+            tags.dedup();
+        }
+        pictures.push(Picture {
+            id: pictures.len() as u32,
+            orientation,
+            tags,
+        });
+    }
+    if pictures.len() < declared_pictures {
+        return Err(ParseError::CountMismatch {
+            line: pictures.len() + 2,
+            declared: declared_pictures,
+            found: pictures.len(),
+        });
+    }
+    if let Some((_, line_number)) = lines.find(|(line, _)| !line.trim().is_empty()) {
+        return Err(ParseError::TrailingData { line: line_number });
+    }
+    Ok(pictures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_inputs_report_their_position() {
+        let cases: &[(&str, bool, &str)] = &[
+            ("x\nH 1 a", false, "BadHeader { line: 1 }"),
+            (
+                "2\nH 1 a\n",
+                false,
+                "CountMismatch { line: 3, declared: 2, found: 1 }",
+            ),
+            (
+                "1\nX 1 a",
+                false,
+                "UnknownOrientation { line: 2, column: 1, found: \"X\" }",
+            ),
+            (
+                "1\n\nH 1 a",
+                false,
+                "UnknownOrientation { line: 2, column: 1, found: \"\" }",
+            ),
+            ("1\nH", false, "BadTagCount { line: 2, column: 2 }"),
+            ("1\nH two a", false, "BadTagCount { line: 2, column: 3 }"),
+            (
+                "1\nH 2 a",
+                false,
+                "TagCountMismatch { line: 2, column: 3, declared: 2, found: 1 }",
+            ),
+            ("1\nH 0", false, "EmptyTag { line: 2, column: 4 }"),
+            (
+                "1\nV 3 a b a",
+                true,
+                "DuplicateTag { line: 2, column: 9, tag: \"a\" }",
+            ),
+            ("1\nH 1 a\nH 1 b", false, "TrailingData { line: 3 }"),
+            (
+                "18446744073709551615\nH 1 a",
+                false,
+                "CountMismatch { line: 3, declared: 18446744073709551615, found: 1 }",
+            ),
+        ];
+        for &(input, strict, expected) in cases.iter() {
+            match parse_input(input.as_bytes(), strict) {
+                Ok(_) => panic!("{:?} should be rejected with {}", input, expected),
+                Err(error) => assert_eq!(format!("{:?}", error), expected, "{:?}", input),
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_tags_are_dropped_unless_strict() {
+        let pictures = parse_input(&b"1\nV 3 a b a\n"[..], false).unwrap();
+        assert_eq!(pictures[0].tags.len(), 2);
+    }
+}
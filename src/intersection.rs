@@ -0,0 +1,156 @@
+//! Counting the elements shared by two sorted lists of distinct integers, with SIMD versions for
+//! x86_64 chosen at runtime.
+//!
+//! The vectorised versions compare a block of one list against every rotation of a block of the
+//! other, then advance past whichever block ends with the smaller element, so each pair of equal
+//! elements is found exactly once. What is left after the last full blocks is merged by the
+//! scalar version.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Number of elements present in both sorted lists, using the fastest version the CPU supports.
+pub fn count_common(left: &[u32], right: &[u32]) -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            //Safe since the CPU supports AVX2
+            return unsafe { count_common_avx2(left, right) };
+        }
+        //SSE2 is part of the x86_64 baseline
+        unsafe { count_common_sse2(left, right) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    count_common_scalar(left, right)
+}
+
+pub fn count_common_scalar(left: &[u32], right: &[u32]) -> u32 {
+    let mut common = 0;
+    let mut left_iter = left.iter();
+    //Since the vectors are sorted, we can traverse each only once
+    if let Some(mut left_element) = left_iter.next() {
+        'outer: for right_element in right.iter() {
+            while left_element < right_element {
+                left_element = match left_iter.next() {
+                    Some(left_element) => left_element,
+                    None => break 'outer,
+                };
+            }
+            if left_element == right_element {
+                common += 1;
+            }
+        }
+    }
+    common
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn count_common_sse2(left: &[u32], right: &[u32]) -> u32 {
+    const LANES: usize = 4;
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut common = 0;
+    while left_index + LANES <= left.len() && right_index + LANES <= right.len() {
+        let left_block = _mm_loadu_si128(left.as_ptr().add(left_index) as *const __m128i);
+        let right_block = _mm_loadu_si128(right.as_ptr().add(right_index) as *const __m128i);
+        let matches = _mm_or_si128(
+            _mm_or_si128(
+                _mm_cmpeq_epi32(left_block, right_block),
+                _mm_cmpeq_epi32(left_block, _mm_shuffle_epi32(right_block, 0b00_11_10_01)),
+            ),
+            _mm_or_si128(
+                _mm_cmpeq_epi32(left_block, _mm_shuffle_epi32(right_block, 0b01_00_11_10)),
+                _mm_cmpeq_epi32(left_block, _mm_shuffle_epi32(right_block, 0b10_01_00_11)),
+            ),
+        );
+        common += _mm_movemask_ps(_mm_castsi128_ps(matches)).count_ones();
+        let left_last = left[left_index + LANES - 1];
+        let right_last = right[right_index + LANES - 1];
+        if left_last <= right_last {
+            left_index += LANES;
+        }
+        if right_last <= left_last {
+            right_index += LANES;
+        }
+    }
+    common + count_common_scalar(&left[left_index..], &right[right_index..])
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn count_common_avx2(left: &[u32], right: &[u32]) -> u32 {
+    const LANES: usize = 8;
+    let rotations = [
+        _mm256_setr_epi32(1, 2, 3, 4, 5, 6, 7, 0),
+        _mm256_setr_epi32(2, 3, 4, 5, 6, 7, 0, 1),
+        _mm256_setr_epi32(3, 4, 5, 6, 7, 0, 1, 2),
+        _mm256_setr_epi32(4, 5, 6, 7, 0, 1, 2, 3),
+        _mm256_setr_epi32(5, 6, 7, 0, 1, 2, 3, 4),
+        _mm256_setr_epi32(6, 7, 0, 1, 2, 3, 4, 5),
+        _mm256_setr_epi32(7, 0, 1, 2, 3, 4, 5, 6),
+    ];
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut common = 0;
+    while left_index + LANES <= left.len() && right_index + LANES <= right.len() {
+        let left_block = _mm256_loadu_si256(left.as_ptr().add(left_index) as *const __m256i);
+        let right_block = _mm256_loadu_si256(right.as_ptr().add(right_index) as *const __m256i);
+        let mut matches = _mm256_cmpeq_epi32(left_block, right_block);
+        for &rotation in rotations.iter() {
+            let rotated_block = _mm256_permutevar8x32_epi32(right_block, rotation);
+            matches = _mm256_or_si256(matches, _mm256_cmpeq_epi32(left_block, rotated_block));
+        }
+        common += _mm256_movemask_ps(_mm256_castsi256_ps(matches)).count_ones();
+        let left_last = left[left_index + LANES - 1];
+        let right_last = right[right_index + LANES - 1];
+        if left_last <= right_last {
+            left_index += LANES;
+        }
+        if right_last <= left_last {
+            right_index += LANES;
+        }
+    }
+    common + count_common_sse2(&left[left_index..], &right[right_index..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Random sorted lists of distinct elements, drawn from a range small enough to share many
+    fn random_sorted_lists(seed: u64, count: usize) -> Vec<Vec<u32>> {
+        let mut state = seed;
+        let mut next = move |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        (0..count)
+            .map(|_| {
+                let range = 1 + next(300);
+                let mut list: Vec<_> = (0..next(120)).map(|_| next(range) as u32).collect();
+                list.sort_unstable();
+                list.dedup();
+                list
+            })
+            .collect()
+    }
+
+    #[test]
+    fn vectorised_versions_match_scalar() {
+        let lists = random_sorted_lists(0x9e37_79b9_7f4a_7c15, 300);
+        for left in lists.iter() {
+            for right in lists.iter() {
+                let expected = count_common_scalar(left, right);
+                assert_eq!(count_common(left, right), expected);
+                #[cfg(target_arch = "x86_64")]
+                unsafe {
+                    assert_eq!(count_common_sse2(left, right), expected);
+                    if is_x86_feature_detected!("avx2") {
+                        assert_eq!(count_common_avx2(left, right), expected);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Solver for the photo slideshow problem of the Google Hash Code 2019 online qualification.
+//!
+//! Pictures are read with [`parse_input`], put on slides with [`create_slides`], ordered with
+//! [`arrange_slides`], improved with [`anneal_slideshow`] and [`improve_slideshow`] and scored
+//! with [`rate_slideshow`]. Slideshows are written in the
+//! submission format with [`write_slides`] and read back with [`read_slides`].
+//! [`calculate_score_bound`] tells how far a score can be from the best possible one.
+
+mod anneal;
+mod bound;
+mod budget;
+mod improve;
+mod input;
+mod intersection;
+mod pairing;
+mod rng;
+mod score;
+mod slides;
+mod submission;
+mod tags;
+
+pub use anneal::{anneal_slideshow, AnnealingSchedule, Cooling, MoveMix};
+pub use bound::{calculate_score_bound, ScoreBound};
+pub use budget::Budget;
+pub use improve::improve_slideshow;
+pub use input::{parse_input, Orientation, ParseError, Picture};
+pub use pairing::{
+    IndexedMinimalOverlap, MinimalOverlap, PairingStrategy, RandomPairing, SampledMatching,
+    SmallWithLarge, TargetTagCount,
+};
+pub use rng::Rng;
+pub use score::{
+    calculate_common_tags, calculate_score, calculate_waste, rate_slideshow, score_upper_bound,
+    waste_lower_bound, Objective,
+};
+pub use slides::{
+    arrange_slides, create_slides, merge_tags, slide_from_pictures, tag_count_histogram,
+    ArrangementOptions, ChainGrowth, Slide,
+};
+pub use submission::{
+    parse_submission, read_slides, validate_submission, write_slides, SubmissionEntry,
+    SubmissionError,
+};
+pub use tags::{vocabulary_size, TagBitset, TagRepresentation, TagSet};
@@ -1,207 +1,895 @@
+use hash_code_2019::{
+    anneal_slideshow, arrange_slides, calculate_score_bound, create_slides, improve_slideshow,
+    parse_input, rate_slideshow, read_slides, tag_count_histogram, write_slides, AnnealingSchedule,
+    ArrangementOptions, Budget, ChainGrowth, Cooling, IndexedMinimalOverlap, MinimalOverlap,
+    MoveMix, Objective, Orientation, PairingStrategy, ParseError, Picture, RandomPairing, Rng,
+    SampledMatching, Slide, SmallWithLarge, SubmissionError, TargetTagCount,
+};
 use rayon::prelude::*;
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
 use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-const PROGRESS_REPORT_INTERVAL: usize = 10000;
-const INPUT: &str = "abcde";
+const DEFAULT_INPUTS: &str = "abcde";
+//Exit status of a process stopped by SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+//Weights of the score and of the waste in the weighted objective when not given
+const DEFAULT_OBJECTIVE_WEIGHTS: (u32, u32) = (2, 1);
+const USAGE: &str =
+    "Usage: hash_code_2019 [solve|validate|score|diff|compare|bound] [OPTIONS] [INPUT]...
+
+Inputs are paths to input files, \"-\" for standard input, or the letter (a, b, c, d or e)
+of one of the datasets in the \"inputs\" folder. All five datasets are used when none are given.
+
+Subcommands:
+    solve       Arrange the slideshows and write them to the output directory (default)
+    validate    Check that the inputs are well-formed without solving them
+    score       Check and score the slideshows already in the output directory
+    diff        Compare the slideshow of a single input with the one given by --against
+    compare     Score the greedy arrangement under each objective, without improving it
+    bound       Compute an upper bound on the score of each input and the gap to it of the
+                slideshow in the output directory, if there is one
+
+Options:
+    --output-dir <DIR>    Directory the output files are written to (default: .)
+    --threads <N>         Number of worker threads (default: one per core)
+    --seed <N>            Seed of every random choice made while solving (default: 0). The
+                          same seed and options always give the same slideshows, whatever
+                          the number of threads
+    --time-limit <SECS>   Time allowed for solving each input. When it runs out the best
+                          slideshow found so far is written. The same happens for the
+                          current input on Ctrl-C, after which the remaining inputs are skipped
+    --pairing <STRATEGY>  How vertical pictures are paired into slides (default: overlap):
+                          overlap      fewest tags with the most tags among those sharing
+                                       the fewest tags with it
+                          indexed      the same, found through an inverted tag index
+                          small-large  fewest tags with most tags, and so on inwards
+                          target       most tags with the picture bringing the slide closest
+                                       to the target tag count
+                          random       in a random order
+                          matching     greedy matching of random pairs by merged tag count
+    --pairing-target <N>  Tag count aimed for by the target pairing strategy (default: the
+                          median tag count of the horizontal pictures)
+    --both-ends           Grow the slideshow by adding slides before its first slide as well
+                          as after its last one, at whichever end is cheaper
+    --objective <COST>    What the greedy arrangement minimises for each slide it adds
+                          (default: waste):
+                          waste     tags of the transition that don't score
+                          score     the score of the transition, negated
+                          weighted  the waste and the negated score, weighted by --weights
+    --weights <S,W>       Weights of the score and of the waste for the weighted objective
+                          (default: 2,1)
+    --lookahead <N>       Compare the N cheapest slides again including the cheapest slide
+                          that could follow them (default: 0, choose the cheapest)
+    --starts <N>          Arrange the slides this many times in parallel from different
+                          random choices and keep the best slideshow (default: 1)
+    --anneal <ITERATIONS> Improve the greedy arrangement with this many iterations of simulated
+                          annealing before the local search (default: 0)
+    --start-temperature <T>
+    --end-temperature <T> Annealing temperatures in points of score (default: 0.5 and 0.01)
+    --cooling <SCHEDULE>  How the temperature decreases: exponential (default) or linear
+    --move-mix <S,R,M>    Relative frequencies of swaps, reversals and moves of runs of slides
+                          tried while annealing (default: 1,1,1)
+    --skip-improvement    Write the arrangement without improving it with local search
+    --initial <FILE>      Improve this slideshow instead of arranging one from scratch.
+                          Only valid with a single input
+    --strict              Reject pictures listing the same tag twice instead of ignoring
+                          the repetition. Always enabled for validate
+    --submission <FILE>   Slideshow to score instead of the one in the output directory.
+                          Only valid with a single input
+    --against <FILE>      Slideshow to compare with, required by diff
+    -h, --help            Print this message";
+
+//Set on Ctrl-C, so that the input being solved is finished with what has been found so far
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
 fn main() {
-    process_inputs(INPUT);
+    let config = match parse_args(env::args().skip(1)) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Some(threads) = config.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .expect("Couldn't create thread pool");
+    }
+    //Only the commands checking the budget can stop early, the others keep the default of exiting
+    if let Command::Solve | Command::Compare | Command::Bound = config.command {
+        ctrlc::set_handler(|| {
+            //A second Ctrl-C stops immediately, e.g. while an input is still being read
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                process::exit(INTERRUPTED_EXIT_CODE);
+            }
+            eprintln!("Interrupted, finishing with the best results found so far");
+        })
+        .expect("Couldn't install Ctrl-C handler");
+    }
+    let success = match config.command {
+        Command::Solve => process_inputs(&config),
+        Command::Validate => validate_inputs(&config),
+        Command::Score => score_submissions(&config),
+        Command::Diff => diff_submissions(&config),
+        Command::Compare => compare_objectives(&config),
+        Command::Bound => bound_scores(&config),
+        Command::Help => {
+            println!("{}", USAGE);
+            true
+        }
+    };
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    if !success {
+        process::exit(1);
+    }
 }
 
 #[derive(Debug)]
-struct Slide {
-    picture_id: u32,
-    second_picture_id: Option<u32>,
-    tags: Vec<u32>,
+enum Command {
+    Solve,
+    Validate,
+    Score,
+    Diff,
+    Compare,
+    Bound,
+    Help,
 }
 
-#[derive(Debug)]
-enum Orientation {
-    Horizontal,
-    Vertical,
+#[derive(Debug, Clone, Copy)]
+enum Pairing {
+    Overlap,
+    Indexed,
+    SmallWithLarge,
+    Random,
+    Matching,
+    Target,
+}
+
+impl Pairing {
+    fn strategy(self, target: Option<usize>, pictures: &[Picture]) -> Box<dyn PairingStrategy> {
+        match self {
+            Pairing::Overlap => Box::new(MinimalOverlap),
+            Pairing::Indexed => Box::new(IndexedMinimalOverlap),
+            Pairing::SmallWithLarge => Box::new(SmallWithLarge),
+            Pairing::Random => Box::new(RandomPairing),
+            Pairing::Matching => Box::new(SampledMatching::default()),
+            Pairing::Target => Box::new(match target {
+                Some(target) => TargetTagCount { target },
+                None => TargetTagCount::for_pictures(pictures),
+            }),
+        }
+    }
 }
 
 #[derive(Debug)]
-struct Picture {
-    id: u32,
-    orientation: Orientation,
-    tags: Vec<u32>,
+struct Config {
+    command: Command,
+    inputs: Vec<String>,
+    output_dir: PathBuf,
+    threads: Option<usize>,
+    seed: u64,
+    strict: bool,
+    time_limit: Option<Duration>,
+    pairing: Pairing,
+    pairing_target: Option<usize>,
+    arrangement: ArrangementOptions,
+    objective_weights: (u32, u32),
+    starts: usize,
+    annealing: AnnealingSchedule,
+    skip_improvement: bool,
+    initial: Option<PathBuf>,
+    submission: Option<PathBuf>,
+    against: Option<PathBuf>,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Config, String> {
+    let mut config = Config {
+        command: Command::Solve,
+        inputs: Vec::new(),
+        output_dir: PathBuf::from("."),
+        threads: None,
+        seed: 0,
+        strict: false,
+        time_limit: None,
+        pairing: Pairing::Overlap,
+        pairing_target: None,
+        arrangement: ArrangementOptions::default(),
+        objective_weights: DEFAULT_OBJECTIVE_WEIGHTS,
+        starts: 1,
+        annealing: AnnealingSchedule {
+            iterations: 0,
+            ..AnnealingSchedule::default()
+        },
+        skip_improvement: false,
+        initial: None,
+        submission: None,
+        against: None,
+    };
+    let mut args = args.peekable();
+    if let Some(command) = args.peek() {
+        let command = match command.as_str() {
+            "solve" => Some(Command::Solve),
+            "validate" => Some(Command::Validate),
+            "score" => Some(Command::Score),
+            "diff" => Some(Command::Diff),
+            "compare" => Some(Command::Compare),
+            "bound" => Some(Command::Bound),
+            _ => None,
+        };
+        if let Some(command) = command {
+            config.command = command;
+            args.next();
+        }
+    }
+    let mut objective_weights = None;
+    while let Some(arg) = args.next() {
+        //Options can be given either as "--name value" or as "--name=value"
+        let (name, inline_value) = match arg.find('=') {
+            Some(index) if arg.starts_with("--") => {
+                (&arg[..index], Some(arg[index + 1..].to_string()))
+            }
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+        match name {
+            "-h" | "--help" => config.command = Command::Help,
+            "--strict" => config.strict = true,
+            "--pairing" => {
+                config.pairing = match value()?.as_str() {
+                    "overlap" => Pairing::Overlap,
+                    "indexed" => Pairing::Indexed,
+                    "small-large" => Pairing::SmallWithLarge,
+                    "random" => Pairing::Random,
+                    "matching" => Pairing::Matching,
+                    "target" => Pairing::Target,
+                    pairing => return Err(format!("Unknown pairing strategy: {}", pairing)),
+                }
+            }
+            "--pairing-target" => config.pairing_target = Some(parse_value(name, &value()?)?),
+            "--both-ends" => config.arrangement.growth = ChainGrowth::BothEnds,
+            "--objective" => {
+                let (score_weight, waste_weight) = DEFAULT_OBJECTIVE_WEIGHTS;
+                config.arrangement.objective = match value()?.as_str() {
+                    "waste" => Objective::Waste,
+                    "score" => Objective::Score,
+                    "weighted" => Objective::Weighted {
+                        score_weight,
+                        waste_weight,
+                    },
+                    objective => return Err(format!("Unknown objective: {}", objective)),
+                }
+            }
+            "--weights" => {
+                let weights = value()?;
+                let parsed: Vec<u32> = weights
+                    .split(',')
+                    .map(|weight| parse_value(name, weight))
+                    .collect::<Result<_, _>>()?;
+                objective_weights = match parsed.as_slice() {
+                    &[score_weight, waste_weight]
+                        if score_weight
+                            .checked_add(waste_weight)
+                            .is_some_and(|total| total > 0) =>
+                    {
+                        Some((score_weight, waste_weight))
+                    }
+                    _ => return Err(format!("Invalid weights: {}", weights)),
+                }
+            }
+            "--lookahead" => config.arrangement.lookahead = parse_value(name, &value()?)?,
+            "--starts" => {
+                let starts = value()?;
+                config.starts = match starts.parse() {
+                    Ok(0) | Err(_) => return Err(format!("Invalid start count: {}", starts)),
+                    Ok(starts) => starts,
+                }
+            }
+            "--anneal" => config.annealing.iterations = parse_value(name, &value()?)?,
+            "--start-temperature" => {
+                config.annealing.start_temperature = parse_value(name, &value()?)?
+            }
+            "--end-temperature" => config.annealing.end_temperature = parse_value(name, &value()?)?,
+            "--cooling" => {
+                config.annealing.cooling = match value()?.as_str() {
+                    "linear" => Cooling::Linear,
+                    "exponential" => Cooling::Exponential,
+                    cooling => return Err(format!("Unknown cooling schedule: {}", cooling)),
+                }
+            }
+            "--move-mix" => {
+                let move_mix = value()?;
+                let weights: Vec<u32> = move_mix
+                    .split(',')
+                    .map(|weight| parse_value(name, weight))
+                    .collect::<Result<_, _>>()?;
+                config.annealing.move_mix = match weights.as_slice() {
+                    //The total is what moves are drawn from, so it must fit in a u32
+                    &[swap, reverse, relocate]
+                        if swap
+                            .checked_add(reverse)
+                            .and_then(|total| total.checked_add(relocate))
+                            .is_some_and(|total| total > 0) =>
+                    {
+                        MoveMix {
+                            swap,
+                            reverse,
+                            relocate,
+                        }
+                    }
+                    _ => return Err(format!("Invalid move mix: {}", move_mix)),
+                }
+            }
+            "--skip-improvement" => config.skip_improvement = true,
+            "--initial" => config.initial = Some(PathBuf::from(value()?)),
+            "--time-limit" => {
+                let time_limit = value()?;
+                config.time_limit = match time_limit.parse().map(Duration::try_from_secs_f64) {
+                    Ok(Ok(time_limit)) => Some(time_limit),
+                    _ => return Err(format!("Invalid time limit: {}", time_limit)),
+                }
+            }
+            "--submission" => config.submission = Some(PathBuf::from(value()?)),
+            "--against" => config.against = Some(PathBuf::from(value()?)),
+            "--output-dir" => config.output_dir = PathBuf::from(value()?),
+            "--threads" => {
+                let threads = value()?;
+                config.threads = match threads.parse() {
+                    Ok(0) | Err(_) => return Err(format!("Invalid thread count: {}", threads)),
+                    Ok(threads) => Some(threads),
+                }
+            }
+            "--seed" => {
+                let seed = value()?;
+                config.seed = seed
+                    .parse()
+                    .map_err(|_| format!("Invalid seed: {}", seed))?;
+            }
+            "-" => config.inputs.push(arg),
+            _ if name.starts_with('-') => return Err(format!("Unknown option: {}", name)),
+            _ => config.inputs.push(arg),
+        }
+    }
+    if let Command::Validate = config.command {
+        config.strict = true;
+    }
+    if config.submission.is_some() && config.inputs.len() != 1 {
+        return Err("--submission requires exactly one input".to_string());
+    }
+    if config.initial.is_some() && config.inputs.len() != 1 {
+        return Err("--initial requires exactly one input".to_string());
+    }
+    if config.pairing_target.is_some() && !matches!(config.pairing, Pairing::Target) {
+        return Err("--pairing-target requires --pairing target".to_string());
+    }
+    if let Some(weights) = objective_weights {
+        //compare tries every objective, so the weights are used there as well
+        let weighted = matches!(config.arrangement.objective, Objective::Weighted { .. });
+        if !weighted && !matches!(config.command, Command::Compare) {
+            return Err("--weights requires --objective weighted".to_string());
+        }
+        config.objective_weights = weights;
+    }
+    if let Objective::Weighted { .. } = config.arrangement.objective {
+        let (score_weight, waste_weight) = config.objective_weights;
+        config.arrangement.objective = Objective::Weighted {
+            score_weight,
+            waste_weight,
+        };
+    }
+    if let Command::Diff = config.command {
+        if config.inputs.len() != 1 || config.against.is_none() {
+            return Err("diff requires exactly one input and --against".to_string());
+        }
+    }
+    let AnnealingSchedule {
+        start_temperature,
+        end_temperature,
+        ..
+    } = config.annealing;
+    if !(start_temperature > 0.0 && end_temperature > 0.0) {
+        return Err("Annealing temperatures must be positive".to_string());
+    }
+    if config.inputs.is_empty() {
+        config.inputs = DEFAULT_INPUTS.chars().map(String::from).collect();
+    }
+    //Inputs sharing an output file would overwrite or be scored against each other's slideshow
+    if let Command::Solve | Command::Score | Command::Bound = config.command {
+        let mut output_names = HashSet::new();
+        for input in config.inputs.iter() {
+            if !output_names.insert(output_name(input)) {
+                return Err(format!(
+                    "{} has the same output file as another input: {}",
+                    input,
+                    output_name(input)
+                ));
+            }
+        }
+    }
+    Ok(config)
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
+
+//Slides paired and arranged from scratch, reporting progress under name
+fn construct_slideshow(
+    config: &Config,
+    pictures: &[Picture],
+    name: &str,
+    arrangement: &ArrangementOptions,
+    pairing_rng: &mut Rng,
+    arrangement_rng: &mut Rng,
+    budget: &Budget,
+) -> Vec<Slide> {
+    let pairing = config.pairing.strategy(config.pairing_target, pictures);
+    let slides = create_slides(pictures, pairing.as_ref(), pairing_rng, budget);
+    let histogram: Vec<_> = tag_count_histogram(&slides)
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, slide_count)| slide_count > 0)
+        .map(|(tag_count, slide_count)| format!("{}: {}", tag_count, slide_count))
+        .collect();
+    println!("Slides by tag count for {}: {}", name, histogram.join(", "));
+    arrange_slides(slides, name, arrangement, arrangement_rng, budget)
 }
 
-fn process_inputs(params: &str) {
+//Returns whether every input could be solved
+fn process_inputs(config: &Config) -> bool {
     let mut total_score = 0;
-    for c in params.chars() {
-        let pictures = parse_input(c);
-        let slides = create_slides(pictures);
-        let arranged_slides = arrange_slides(slides, c);
+    let mut success = true;
+    //Still solve every input, so the scores are reported even if they can't be written
+    if let Err(error) = fs::create_dir_all(&config.output_dir) {
+        eprintln!(
+            "Couldn't create output directory {}: {}",
+            config.output_dir.display(),
+            error
+        );
+        success = false;
+    }
+    for input in config.inputs.iter() {
+        let pictures = match load_input(input, config.strict) {
+            Some(pictures) => pictures,
+            None => {
+                success = false;
+                continue;
+            }
+        };
+        let budget = budget(config);
+        //Every step draws from its own stream, so enabling one doesn't change the others
+        let mut rng = Rng::new(config.seed);
+        let (mut pairing_rng, mut arrangement_rng, mut annealing_rng) =
+            (rng.fork(), rng.fork(), rng.fork());
+        let mut arranged_slides = match config.initial {
+            Some(ref initial) => match load_submission(initial, &pictures) {
+                Some(slides) => slides,
+                None => {
+                    success = false;
+                    continue;
+                }
+            },
+            None => {
+                let start_rngs: Vec<_> = (0..config.starts)
+                    .map(|_| (pairing_rng.fork(), arrangement_rng.fork()))
+                    .collect();
+                let (score, cmp::Reverse(best_start), slides) = start_rngs
+                    .into_par_iter()
+                    .enumerate()
+                    .map(|(start, (mut pairing_rng, mut arrangement_rng))| {
+                        let name = match config.starts {
+                            1 => input.to_string(),
+                            _ => format!("{} (start {})", input, start + 1),
+                        };
+                        let slides = construct_slideshow(
+                            config,
+                            &pictures,
+                            &name,
+                            &config.arrangement,
+                            &mut pairing_rng,
+                            &mut arrangement_rng,
+                            &budget,
+                        );
+                        (rate_slideshow(&slides), cmp::Reverse(start), slides)
+                    })
+                    .max_by_key(|&(score, start, _)| (score, start))
+                    .expect("There is at least one start");
+                if config.starts > 1 {
+                    println!(
+                        "Best of {} starts for {}: start {} with {}",
+                        config.starts,
+                        input,
+                        best_start + 1,
+                        score
+                    );
+                }
+                slides
+            }
+        };
+        if config.annealing.iterations > 0 {
+            arranged_slides = anneal_slideshow(
+                arranged_slides,
+                input,
+                &config.annealing,
+                &mut annealing_rng,
+                &budget,
+            );
+        }
+        if !config.skip_improvement {
+            arranged_slides = improve_slideshow(arranged_slides, &pictures, input, &budget);
+        }
         let score = rate_slideshow(&arranged_slides);
         total_score += score;
-        write_slides(&arranged_slides, format!("output_{}.txt", c).as_str());
-        println!("Score for {}: {}", c, score);
+        let output_path = config.output_dir.join(output_name(input));
+        if let Err(error) = write_slides(&arranged_slides, &output_path) {
+            eprintln!("Couldn't write {}: {}", output_path.display(), error);
+            success = false;
+        }
+        println!("Score for {}: {}", input, score);
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            break;
+        }
     }
     println!("Total score: {}", total_score);
+    success
 }
 
-fn arrange_slides(mut slides: Vec<Slide>, name: char) -> Vec<Slide> {
-    let mut arranged_slides: Vec<Slide> = Vec::with_capacity(slides.len());
-    let mut current_slide_index = 0;
-    while !slides.is_empty() {
-        if slides.len() % PROGRESS_REPORT_INTERVAL == 0 {
-            println!("Slides remaining for {}: {}", name, slides.len());
+//Arrange each input greedily under every objective and report the scores, so they can be
+//compared without local search or annealing blurring the difference
+fn compare_objectives(config: &Config) -> bool {
+    let (score_weight, waste_weight) = config.objective_weights;
+    let objectives = [
+        ("waste", Objective::Waste),
+        ("score", Objective::Score),
+        (
+            "weighted",
+            Objective::Weighted {
+                score_weight,
+                waste_weight,
+            },
+        ),
+    ];
+    let mut total_scores = [0; 3];
+    let mut success = true;
+    for input in config.inputs.iter() {
+        let pictures = match load_input(input, config.strict) {
+            Some(pictures) => pictures,
+            None => {
+                success = false;
+                continue;
+            }
+        };
+        let mut scores = [0; 3];
+        for (&(objective_name, objective), score) in objectives.iter().zip(scores.iter_mut()) {
+            let budget = budget(config);
+            //The same random choices as the first start of solve
+            let mut rng = Rng::new(config.seed);
+            let (mut pairing_rng, mut arrangement_rng) = (rng.fork(), rng.fork());
+            let slides = construct_slideshow(
+                config,
+                &pictures,
+                &format!("{} ({})", input, objective_name),
+                &ArrangementOptions {
+                    objective,
+                    ..config.arrangement
+                },
+                &mut pairing_rng.fork(),
+                &mut arrangement_rng.fork(),
+                &budget,
+            );
+            *score = rate_slideshow(&slides);
+        }
+        let best = (0..objectives.len())
+            .max_by_key(|&index| (scores[index], cmp::Reverse(index)))
+            .expect("There are objectives");
+        println!(
+            "Scores for {}: waste {}, score {}, weighted {} (best: {})",
+            input, scores[0], scores[1], scores[2], objectives[best].0
+        );
+        for (total_score, score) in total_scores.iter_mut().zip(scores.iter()) {
+            *total_score += score;
         }
-        let current_slide = slides.remove(current_slide_index);
-        current_slide_index = slides
-            .par_iter()
-            .enumerate()
-            .min_by_key(|(_, potential_match)| {
-                calculate_waste(&current_slide.tags, &potential_match.tags)
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    println!(
+        "Total scores: waste {}, score {}, weighted {}",
+        total_scores[0], total_scores[1], total_scores[2]
+    );
+    success
+}
+
+//Report an upper bound on the score of each input, and how far the current slideshow is from it
+fn bound_scores(config: &Config) -> bool {
+    let (mut total_bound, mut total_score) = (0, 0);
+    //Bound of the inputs with a submission, which the total score is compared with
+    let mut scored_bound = 0;
+    let mut success = true;
+    for input in config.inputs.iter() {
+        let pictures = match load_input(input, config.strict) {
+            Some(pictures) => pictures,
+            None => {
+                success = false;
+                continue;
+            }
+        };
+        let budget = budget(config);
+        let bound = calculate_score_bound(&pictures, &budget);
+        let bound_value = bound.value();
+        total_bound += bound_value;
+        match bound.degree {
+            Some(degree) => println!(
+                "Upper bound for {}: {} (path {}, degree {})",
+                input, bound_value, bound.path, degree
+            ),
+            None => println!("Upper bound for {}: {}", input, bound_value),
+        }
+        let submission_path = submission_path(config, input);
+        if submission_path.exists() {
+            let slides = match load_submission(&submission_path, &pictures) {
+                Some(slides) => slides,
+                None => {
+                    success = false;
+                    continue;
+                }
+            };
+            let score = rate_slideshow(&slides);
+            total_score += score;
+            scored_bound += bound_value;
+            println!(
+                "Gap for {}: {} scores {}, {} below the bound ({:.1}% of it)",
+                input,
+                submission_path.display(),
+                score,
+                bound_value - score,
+                percentage(score, bound_value)
+            );
+        }
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    println!(
+        "Total upper bound: {}, {} scored so far ({:.1}% of the bound of the scored inputs)",
+        total_bound,
+        total_score,
+        percentage(total_score, scored_bound)
+    );
+    success
+}
+
+fn percentage(score: u32, bound: u32) -> f64 {
+    match bound {
+        0 => 100.0,
+        _ => 100.0 * f64::from(score) / f64::from(bound),
+    }
+}
+
+//Check that the inputs can be read and report what they contain, without solving them
+fn validate_inputs(config: &Config) -> bool {
+    let mut success = true;
+    for input in config.inputs.iter() {
+        let pictures = match load_input(input, config.strict) {
+            Some(pictures) => pictures,
+            None => {
+                success = false;
+                continue;
+            }
+        };
+        let vertical_pictures = pictures
+            .iter()
+            .filter(|picture| match picture.orientation {
+                Orientation::Horizontal => false,
+                Orientation::Vertical => true,
             })
-            .map(|(index, _)| index)
-            .unwrap_or(0);
-        arranged_slides.push(current_slide);
+            .count();
+        println!(
+            "Input {}: {} pictures ({} horizontal, {} vertical)",
+            input,
+            pictures.len(),
+            pictures.len() - vertical_pictures,
+            vertical_pictures
+        );
     }
-    arranged_slides
+    success
 }
 
-fn calculate_common_tags(left_tags: &[u32], right_tags: &[u32]) -> u32 {
-    let mut common_tags = 0;
-    let mut left_iter = left_tags.iter();
-    //Since the vectors are sorted, we can traverse each only once
-    if let Some(mut left_tag) = left_iter.next() {
-        'outer: for right_tag in right_tags.iter() {
-            while left_tag < right_tag {
-                left_tag = match left_iter.next() {
-                    Some(left_tag) => left_tag,
-                    None => break 'outer,
-                };
+//Check the slideshows previously written for the inputs and report their scores
+fn score_submissions(config: &Config) -> bool {
+    let mut total_score = 0;
+    let mut success = true;
+    for input in config.inputs.iter() {
+        let pictures = match load_input(input, config.strict) {
+            Some(pictures) => pictures,
+            None => {
+                success = false;
+                continue;
             }
-            if left_tag == right_tag {
-                common_tags += 1;
+        };
+        let submission_path = submission_path(config, input);
+        let slides = match load_submission(&submission_path, &pictures) {
+            Some(slides) => slides,
+            None => {
+                success = false;
+                continue;
             }
+        };
+        let score = rate_slideshow(&slides);
+        total_score += score;
+        println!(
+            "Score for {}: {} ({} slides)",
+            submission_path.display(),
+            score,
+            slides.len()
+        );
+    }
+    println!("Total score: {}", total_score);
+    success
+}
+
+//Compare the slideshow in the output directory (or --submission) with the one given by --against
+fn diff_submissions(config: &Config) -> bool {
+    let input = &config.inputs[0];
+    let pictures = match load_input(input, config.strict) {
+        Some(pictures) => pictures,
+        None => return false,
+    };
+    let submission_path = submission_path(config, input);
+    let against_path = config.against.as_ref().expect("diff requires --against");
+    let (slides, other_slides) = match (
+        load_submission(&submission_path, &pictures),
+        load_submission(against_path, &pictures),
+    ) {
+        (Some(slides), Some(other_slides)) => (slides, other_slides),
+        _ => return false,
+    };
+    //Slides and transitions are compared regardless of the order of the pictures within them
+    let slide_key = |slide: &Slide| match slide.second_picture_id {
+        Some(second_picture_id) if second_picture_id < slide.picture_id => {
+            (second_picture_id, Some(slide.picture_id))
         }
+        second_picture_id => (slide.picture_id, second_picture_id),
+    };
+    let transitions = |slides: &[Slide]| -> HashSet<_> {
+        slides
+            .windows(2)
+            .map(|pair| {
+                let (left, right) = (slide_key(&pair[0]), slide_key(&pair[1]));
+                (cmp::min(left, right), cmp::max(left, right))
+            })
+            .collect()
+    };
+    let keys: HashSet<_> = slides.iter().map(slide_key).collect();
+    let common_slides = other_slides
+        .iter()
+        .filter(|slide| keys.contains(&slide_key(slide)))
+        .count();
+    let common_transitions = transitions(&slides)
+        .intersection(&transitions(&other_slides))
+        .count();
+    let (score, other_score) = (rate_slideshow(&slides), rate_slideshow(&other_slides));
+    println!(
+        "{}: {} slides, score {}",
+        submission_path.display(),
+        slides.len(),
+        score
+    );
+    println!(
+        "{}: {} slides, score {}",
+        against_path.display(),
+        other_slides.len(),
+        other_score
+    );
+    println!(
+        "Score difference: {}",
+        i64::from(other_score) - i64::from(score)
+    );
+    println!(
+        "Common slides: {}, common transitions: {}",
+        common_slides, common_transitions
+    );
+    true
+}
+
+//Budget for working on one input, running out after the time limit or on Ctrl-C
+fn budget(config: &Config) -> Budget {
+    match config.time_limit {
+        Some(time_limit) => Budget::with_time_limit(time_limit),
+        None => Budget::unlimited(),
     }
-    common_tags
+    .interruptible(&INTERRUPTED)
 }
 
-fn calculate_score(left_tags: &[u32], right_tags: &[u32]) -> u32 {
-    let common_tags = calculate_common_tags(left_tags, right_tags);
-    let left_side = left_tags.len() as u32 - common_tags;
-    let right_side = right_tags.len() as u32 - common_tags;
-    cmp::min(common_tags, cmp::min(left_side, right_side))
+fn submission_path(config: &Config, input: &str) -> PathBuf {
+    config
+        .submission
+        .clone()
+        .unwrap_or_else(|| config.output_dir.join(output_name(input)))
 }
 
-fn calculate_waste(left_tags: &[u32], right_tags: &[u32]) -> u32 {
-    let common_tags = calculate_common_tags(left_tags, right_tags);
-    let left_side = left_tags.len() as u32 - common_tags;
-    let right_side = right_tags.len() as u32 - common_tags;
-    let score = cmp::min(common_tags, cmp::min(left_side, right_side));
-    left_side - score + right_side - score + common_tags - score
+//Read a submission, printing a diagnostic instead of failing when it is missing or invalid
+fn load_submission(path: &Path, pictures: &[Picture]) -> Option<Vec<Slide>> {
+    match fs::File::open(path)
+        .map_err(SubmissionError::from)
+        .and_then(|file| read_slides(file, pictures))
+    {
+        Ok(slides) => Some(slides),
+        Err(error) => {
+            eprintln!("Invalid submission {}: {}", path.display(), error);
+            None
+        }
+    }
 }
 
-//Write output to file
-fn write_slides(slides: &[Slide], filename: &str) {
-    let output = slides
-        .iter()
-        .fold(slides.len().to_string(), |output, slide| {
-            if let Some(second_picture_id) = slide.second_picture_id {
-                output + format!("\n{} {}", slide.picture_id, second_picture_id).as_str()
-            } else {
-                output + format!("\n{}", slide.picture_id).as_str()
-            }
-        });
-    fs::write(filename, output).expect("Couldn't write output");
+//Resolve the dataset letters to the files of the original problem, anything else is a path
+fn input_path(input: &str) -> PathBuf {
+    let dataset_name = match input {
+        "a" => "a_example.txt",
+        "b" => "b_lovely_landscapes.txt",
+        "c" => "c_memorable_moments.txt",
+        "d" => "d_pet_pictures.txt",
+        "e" => "e_shiny_selfies.txt",
+        _ => return PathBuf::from(input),
+    };
+    Path::new("inputs").join(dataset_name)
 }
 
-fn rate_slideshow(slides: &[Slide]) -> u32 {
-    slides.windows(2).fold(0, |score, slide_pair| {
-        calculate_score(&slide_pair[0].tags, &slide_pair[1].tags) + score
-    })
+fn open_input(input: &str) -> io::Result<Box<dyn Read>> {
+    if input == "-" {
+        return Ok(Box::new(io::stdin()));
+    }
+    Ok(Box::new(fs::File::open(input_path(input))?))
 }
 
-//Create slides from pictures
-fn create_slides(pictures: Vec<Picture>) -> Vec<Slide> {
-    let (horizontal_pictures, mut vertical_pictures): (Vec<_>, Vec<_>) = pictures
-        .into_iter()
-        .partition(|picture| match picture.orientation {
-            Orientation::Horizontal => true,
-            Orientation::Vertical => false,
-        });
-    let mut slides: Vec<_> = horizontal_pictures
-        .into_iter()
-        .map(|picture| Slide {
-            picture_id: picture.id,
-            second_picture_id: None,
-            tags: picture.tags,
-        })
-        .collect();
-    vertical_pictures.sort_unstable_by_key(|picture| picture.tags.len());
-    vertical_pictures.reverse();
-    while let Some(mut current_picture) = vertical_pictures.pop() {
-        let mut smallest_waste_index = 0;
-        let mut smallest_waste = u32::max_value();
-        for (index, picture) in vertical_pictures.iter().enumerate() {
-            let waste = calculate_common_tags(&current_picture.tags, &picture.tags);
-            if waste < smallest_waste {
-                smallest_waste = waste;
-                smallest_waste_index = index;
-            }
-            if waste == 0 {
-                break;
-            }
-        }
-        let mut matching_picture = vertical_pictures.remove(smallest_waste_index);
-        current_picture.tags.append(&mut matching_picture.tags);
-        current_picture.tags.dedup();
-        current_picture.tags.sort_unstable();
-        slides.push(Slide {
-            picture_id: current_picture.id,
-            second_picture_id: Option::Some(matching_picture.id),
-            tags: current_picture.tags,
-        })
+//Parse an input, printing a diagnostic instead of failing when it is missing or malformed
+fn load_input(input: &str, strict: bool) -> Option<Vec<Picture>> {
+    match open_input(input)
+        .map_err(ParseError::from)
+        .and_then(|reader| parse_input(reader, strict))
+    {
+        Ok(pictures) => Some(pictures),
+        Err(ParseError::Io(error)) => {
+            eprintln!(
+                "Couldn't open {}: {}. Put the dataset files in \"inputs\" folder",
+                input_path(input).display(),
+                error
+            );
+            None
+        }
+        Err(error) => {
+            eprintln!("Invalid input {}: {}", input, error);
+            None
+        }
     }
-    slides
 }
 
-fn parse_input(input_number: char) -> (Vec<Picture>) {
-    let input_name = match input_number {
-        'a' => "a_example.txt",
-        'b' => "b_lovely_landscapes.txt",
-        'c' => "c_memorable_moments.txt",
-        'd' => "d_pet_pictures.txt",
-        'e' => "e_shiny_selfies.txt",
-        _ => panic!("Wrong input"),
+//Name of the output file for an input: "output_a.txt" for dataset a, "output_foo.txt" for foo.in
+fn output_name(input: &str) -> String {
+    let stem = match input {
+        "-" => "stdin",
+        _ if input_path(input) != Path::new(input) => input,
+        _ => Path::new(input)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(input),
     };
-    let file = fs::read_to_string(format!("inputs/{}", input_name))
-        .expect("Couldn't find input files. Put input files in \"inputs\" folder");
-    let lines = file.lines();
-    let mut tag_map = HashMap::new();
-    lines
-        .skip(1) //First line has no picture data in it
-        .enumerate()
-        .map(|(picture_number, line)| {
-            let mut words = line.split_whitespace();
-            let id = picture_number as u32;
-            let orientation = match words.next().expect("Missing orientation information") {
-                "H" => Orientation::Horizontal,
-                "V" => Orientation::Vertical,
-                _ => panic!("Invalid orientation"),
-            };
-            let mut tags: Vec<_> = words
-                .skip(1) //The next word is the number of tags in a picture, which is known from the size of the Vec anyway
-                .map(|tag| {
-                    let numerical_tag = tag_map.len() as u32;
-                    *tag_map.entry(tag).or_insert(numerical_tag)
-                })
-                .collect();
-            //Sort tags to enable faster calculation of common_tags
-            tags.sort_unstable();
-            Picture {
-                id,
-                orientation,
-                tags,
-            }
-        })
-        .collect()
+    format!("output_{}.txt", stem)
 }
@@ -0,0 +1,310 @@
+//! Strategies for pairing up vertical pictures into slides.
+
+use crate::tags::index_by_tag;
+use crate::{calculate_common_tags, Budget, Orientation, Picture, Rng};
+use std::cmp;
+
+//Partners compared by TargetTagCount for each picture, from the closest tag counts
+const TARGET_CANDIDATES: usize = 32;
+
+/// A way of choosing which vertical pictures share a slide.
+pub trait PairingStrategy {
+    /// Pairs of indices into `pictures`, each picture used at most once. Random choices are
+    /// drawn from `rng`. Once the budget is exhausted the remaining pictures should be paired as
+    /// quickly as possible.
+    fn pair(&self, pictures: &[&Picture], rng: &mut Rng, budget: &Budget) -> Vec<(usize, usize)>;
+}
+
+//Indices of the pictures from the most to the fewest tags
+fn by_descending_tag_count(pictures: &[&Picture]) -> Vec<usize> {
+    let mut order: Vec<_> = (0..pictures.len()).collect();
+    order.sort_unstable_by_key(|&index| cmp::Reverse(pictures[index].tags.len()));
+    order
+}
+
+//Pair the picture with the fewest tags remaining with the one chosen by find_partner among the
+//unpaired positions of order, until fewer than two are left
+fn pair_smallest_first(
+    pictures: &[&Picture],
+    budget: &Budget,
+    mut find_partner: impl FnMut(usize, &[usize], &[bool], usize, usize) -> usize,
+) -> Vec<(usize, usize)> {
+    let order = by_descending_tag_count(pictures);
+    //Pictures stay in place once paired, the unpaired ones all lie within first..last
+    let mut paired = vec![false; order.len()];
+    let (mut first, mut last) = (0, order.len());
+    let mut unpaired = order.len();
+    let mut pairs = Vec::with_capacity(order.len() / 2);
+    //With an odd number of vertical pictures the last one can't be used
+    while unpaired >= 2 {
+        while paired[last - 1] {
+            last -= 1;
+        }
+        let current_position = last - 1;
+        paired[current_position] = true;
+        while paired[first] {
+            first += 1;
+        }
+        let matching_position = if budget.is_exhausted() {
+            //Out of time, take the next picture in order
+            (first..last)
+                .rev()
+                .find(|&position| !paired[position])
+                .expect("There are pictures unpaired")
+        } else {
+            find_partner(current_position, &order, &paired, first, last)
+        };
+        paired[matching_position] = true;
+        unpaired -= 2;
+        pairs.push((order[current_position], order[matching_position]));
+    }
+    pairs
+}
+
+/// Pair the picture with the fewest tags with the one with the most tags among those sharing the
+/// fewest tags with it, so that merged slides have as many tags as possible.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MinimalOverlap;
+
+impl PairingStrategy for MinimalOverlap {
+    fn pair(&self, pictures: &[&Picture], _rng: &mut Rng, budget: &Budget) -> Vec<(usize, usize)> {
+        pair_smallest_first(pictures, budget, |current, order, paired, first, last| {
+            let current_tags = &pictures[order[current]].tags;
+            let mut smallest_overlap_position = first;
+            let mut smallest_overlap = u32::MAX;
+            for position in (first..last).filter(|&position| !paired[position]) {
+                let overlap = calculate_common_tags(current_tags, &pictures[order[position]].tags);
+                if overlap < smallest_overlap {
+                    smallest_overlap = overlap;
+                    smallest_overlap_position = position;
+                }
+                if overlap == 0 {
+                    break;
+                }
+            }
+            smallest_overlap_position
+        })
+    }
+}
+
+/// The same pairs as `MinimalOverlap`, with overlaps counted through an inverted tag index so
+/// that pictures sharing no tags are found without comparing tag lists.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IndexedMinimalOverlap;
+
+impl PairingStrategy for IndexedMinimalOverlap {
+    fn pair(&self, pictures: &[&Picture], _rng: &mut Rng, budget: &Budget) -> Vec<(usize, usize)> {
+        let order = by_descending_tag_count(pictures);
+        let mut positions_by_tag =
+            index_by_tag(order.iter().map(|&index| pictures[index].tags.as_slice()));
+        let mut overlaps = vec![0u32; pictures.len()];
+        let mut overlapping = Vec::new();
+        pair_smallest_first(pictures, budget, |current, order, paired, first, last| {
+            for &tag in pictures[order[current]].tags.iter() {
+                let tag_positions = &mut positions_by_tag[tag as usize];
+                tag_positions.retain(|&position| !paired[position as usize]);
+                for &position in tag_positions.iter() {
+                    if overlaps[position as usize] == 0 {
+                        overlapping.push(position as usize);
+                    }
+                    overlaps[position as usize] += 1;
+                }
+            }
+            let partner = (first..last)
+                .find(|&position| !paired[position] && overlaps[position] == 0)
+                .or_else(|| {
+                    overlapping
+                        .iter()
+                        .cloned()
+                        .min_by_key(|&position| (overlaps[position], position))
+                })
+                .expect("There are pictures unpaired");
+            for position in overlapping.drain(..) {
+                overlaps[position] = 0;
+            }
+            partner
+        })
+    }
+}
+
+/// Pair the picture with the fewest tags with the one with the most, and so on inwards, so that
+/// merged slides have similar tag counts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmallWithLarge;
+
+impl PairingStrategy for SmallWithLarge {
+    fn pair(&self, pictures: &[&Picture], _rng: &mut Rng, _budget: &Budget) -> Vec<(usize, usize)> {
+        let order = by_descending_tag_count(pictures);
+        (0..order.len() / 2)
+            .map(|position| (order[order.len() - 1 - position], order[position]))
+            .collect()
+    }
+}
+
+/// Pair the pictures in a random order.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomPairing;
+
+impl PairingStrategy for RandomPairing {
+    fn pair(&self, pictures: &[&Picture], rng: &mut Rng, _budget: &Budget) -> Vec<(usize, usize)> {
+        let mut order: Vec<_> = (0..pictures.len()).collect();
+        rng.shuffle(&mut order);
+        order
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect()
+    }
+}
+
+/// Greedy maximum weight matching on a graph linking every picture to `sample_size` random
+/// others, weighted by the number of tags the merged slide would have.
+#[derive(Debug, Clone, Copy)]
+pub struct SampledMatching {
+    pub sample_size: usize,
+}
+
+impl Default for SampledMatching {
+    fn default() -> Self {
+        SampledMatching { sample_size: 16 }
+    }
+}
+
+impl PairingStrategy for SampledMatching {
+    fn pair(&self, pictures: &[&Picture], rng: &mut Rng, budget: &Budget) -> Vec<(usize, usize)> {
+        if pictures.len() < 2 {
+            return Vec::new();
+        }
+        let mut edges = Vec::with_capacity(pictures.len() * self.sample_size);
+        for (index, picture) in pictures.iter().enumerate() {
+            if budget.is_exhausted() {
+                break;
+            }
+            for _ in 0..self.sample_size {
+                let other = rng.below(pictures.len());
+                if other == index {
+                    continue;
+                }
+                let other_tags = &pictures[other].tags;
+                let merged_tag_count = picture.tags.len() + other_tags.len()
+                    - calculate_common_tags(&picture.tags, other_tags) as usize;
+                edges.push((cmp::Reverse(merged_tag_count), index, other));
+            }
+        }
+        edges.sort_unstable();
+        let mut paired = vec![false; pictures.len()];
+        let mut pairs = Vec::with_capacity(pictures.len() / 2);
+        for &(_, index, other) in edges.iter() {
+            if !paired[index] && !paired[other] {
+                paired[index] = true;
+                paired[other] = true;
+                pairs.push((index, other));
+            }
+        }
+        //Pictures whose sampled partners were all taken are paired among themselves
+        let unpaired: Vec<_> = (0..pictures.len())
+            .filter(|&index| !paired[index])
+            .collect();
+        pairs.extend(unpaired.chunks_exact(2).map(|pair| (pair[0], pair[1])));
+        pairs
+    }
+}
+
+/// Pair the picture with the most tags with one bringing the merged slide as close as possible
+/// to `target` tags, preferring pictures with the complementary tag count.
+///
+/// A transition scores at most half the tags of the smaller slide, so slides with similar tag
+/// counts waste less of them.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetTagCount {
+    pub target: usize,
+}
+
+impl TargetTagCount {
+    /// Target the median tag count of the horizontal pictures, or twice that of the vertical ones
+    /// when there are no horizontal pictures.
+    pub fn for_pictures(pictures: &[Picture]) -> Self {
+        let median_tag_count = |orientation: Orientation| {
+            let mut tag_counts: Vec<_> = pictures
+                .iter()
+                .filter(|picture| picture.orientation == orientation)
+                .map(|picture| picture.tags.len())
+                .collect();
+            tag_counts.sort_unstable();
+            tag_counts.get(tag_counts.len() / 2).cloned()
+        };
+        let target = median_tag_count(Orientation::Horizontal)
+            .or_else(|| median_tag_count(Orientation::Vertical).map(|tag_count| 2 * tag_count))
+            .unwrap_or(0);
+        TargetTagCount { target }
+    }
+}
+
+impl PairingStrategy for TargetTagCount {
+    fn pair(&self, pictures: &[&Picture], _rng: &mut Rng, budget: &Budget) -> Vec<(usize, usize)> {
+        //Unpaired pictures by tag count, and where each one is in its list
+        let max_tag_count = pictures.iter().map(|picture| picture.tags.len()).max();
+        let mut by_tag_count = vec![Vec::new(); max_tag_count.map_or(0, |count| count + 1)];
+        let mut list_position = vec![0; pictures.len()];
+        for (index, picture) in pictures.iter().enumerate() {
+            let list = &mut by_tag_count[picture.tags.len()];
+            list_position[index] = list.len();
+            list.push(index);
+        }
+        let remove =
+            |index: usize, by_tag_count: &mut [Vec<usize>], list_position: &mut [usize]| {
+                let list = &mut by_tag_count[pictures[index].tags.len()];
+                list.swap_remove(list_position[index]);
+                if let Some(&moved) = list.get(list_position[index]) {
+                    list_position[moved] = list_position[index];
+                }
+            };
+        let mut pairs = Vec::with_capacity(pictures.len() / 2);
+        for index in by_descending_tag_count(pictures) {
+            let tags = &pictures[index].tags;
+            if by_tag_count[tags.len()].get(list_position[index]) != Some(&index) {
+                continue;
+            }
+            remove(index, &mut by_tag_count, &mut list_position);
+            let candidate_limit = if budget.is_exhausted() {
+                1
+            } else {
+                TARGET_CANDIDATES
+            };
+            //Visit the tag counts by distance to the one completing the target, scoring each
+            //candidate by how far from the target the merged slide would be and then by overlap
+            let complement = self.target.saturating_sub(tags.len());
+            let mut best: Option<(usize, u32, usize)> = None;
+            let mut examined = 0;
+            for distance in 0..=cmp::max(complement, by_tag_count.len()) {
+                let tag_counts = [
+                    Some(complement + distance),
+                    complement.checked_sub(distance).filter(|_| distance > 0),
+                ];
+                for tag_count in tag_counts.iter() {
+                    let list = match tag_count.and_then(|tag_count| by_tag_count.get(tag_count)) {
+                        Some(list) => list,
+                        None => continue,
+                    };
+                    for &other in list.iter().rev().take(candidate_limit - examined) {
+                        let overlap = calculate_common_tags(tags, &pictures[other].tags);
+                        let merged_tag_count =
+                            tags.len() + pictures[other].tags.len() - overlap as usize;
+                        let deviation = merged_tag_count.abs_diff(self.target);
+                        let candidate = (deviation, overlap, other);
+                        best = Some(best.map_or(candidate, |best| cmp::min(best, candidate)));
+                        examined += 1;
+                    }
+                }
+                if examined >= candidate_limit || best.is_some_and(|(deviation, ..)| deviation == 0)
+                {
+                    break;
+                }
+            }
+            if let Some((_, _, other)) = best {
+                remove(other, &mut by_tag_count, &mut list_position);
+                pairs.push((index, other));
+            }
+        }
+        pairs
+    }
+}
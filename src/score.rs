@@ -0,0 +1,97 @@
+//! Scoring of transitions between slides and of whole slideshows.
+
+use crate::{intersection, Slide, TagSet};
+use std::cmp;
+
+/// Number of tags present in both sorted tag lists.
+pub fn calculate_common_tags(left_tags: &[u32], right_tags: &[u32]) -> u32 {
+    intersection::count_common(left_tags, right_tags)
+}
+
+/// Interest factor of a transition: the smallest of the common tags and the tags only on either side.
+pub fn calculate_score<T: TagSet + ?Sized>(left_tags: &T, right_tags: &T) -> u32 {
+    score_and_waste(left_tags, right_tags).0
+}
+
+/// Tags of a transition that don't contribute to its score, used as the greedy arrangement criterion.
+pub fn calculate_waste<T: TagSet + ?Sized>(left_tags: &T, right_tags: &T) -> u32 {
+    score_and_waste(left_tags, right_tags).1
+}
+
+//Score and waste of a transition, from a single count of the common tags
+fn score_and_waste<T: TagSet + ?Sized>(left_tags: &T, right_tags: &T) -> (u32, u32) {
+    let common_tags = left_tags.common_tags(right_tags);
+    let left_side = left_tags.tag_count() as u32 - common_tags;
+    let right_side = right_tags.tag_count() as u32 - common_tags;
+    let score = cmp::min(common_tags, cmp::min(left_side, right_side));
+    let waste = left_side - score + right_side - score + common_tags - score;
+    (score, waste)
+}
+
+/// Smallest waste a transition between slides with these numbers of tags can have.
+///
+/// It is reached when the smaller slide shares half its tags with the larger one, which is also
+/// the highest score such a transition can have.
+pub fn waste_lower_bound(left_tag_count: usize, right_tag_count: usize) -> u32 {
+    let smaller = cmp::min(left_tag_count, right_tag_count);
+    let larger = cmp::max(left_tag_count, right_tag_count);
+    (larger - smaller + smaller % 2) as u32
+}
+
+/// Highest score a transition between slides with these numbers of tags can have.
+pub fn score_upper_bound(left_tag_count: usize, right_tag_count: usize) -> u32 {
+    (cmp::min(left_tag_count, right_tag_count) / 2) as u32
+}
+
+/// What the greedy arrangement optimises for each transition it adds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    /// Fewest tags not contributing to the score, which favours slides with few tags
+    Waste,
+    /// Highest score, whatever the number of tags wasted
+    Score,
+    /// Highest `score_weight` times the score minus `waste_weight` times the waste
+    Weighted {
+        score_weight: u32,
+        waste_weight: u32,
+    },
+}
+
+impl Objective {
+    /// Cost of a transition under the objective, lower being better.
+    pub fn cost<T: TagSet + ?Sized>(self, left_tags: &T, right_tags: &T) -> i64 {
+        match self {
+            Objective::Waste => i64::from(calculate_waste(left_tags, right_tags)),
+            Objective::Score => -i64::from(calculate_score(left_tags, right_tags)),
+            Objective::Weighted {
+                score_weight,
+                waste_weight,
+            } => {
+                let (score, waste) = score_and_waste(left_tags, right_tags);
+                i64::from(waste_weight) * i64::from(waste)
+                    - i64::from(score_weight) * i64::from(score)
+            }
+        }
+    }
+
+    /// Lowest cost a transition between slides with these numbers of tags can have.
+    pub fn cost_lower_bound(self, left_tag_count: usize, right_tag_count: usize) -> i64 {
+        let waste = i64::from(waste_lower_bound(left_tag_count, right_tag_count));
+        let score = i64::from(score_upper_bound(left_tag_count, right_tag_count));
+        match self {
+            Objective::Waste => waste,
+            Objective::Score => -score,
+            Objective::Weighted {
+                score_weight,
+                waste_weight,
+            } => i64::from(waste_weight) * waste - i64::from(score_weight) * score,
+        }
+    }
+}
+
+/// Total score of a slideshow, summed over every pair of consecutive slides.
+pub fn rate_slideshow(slides: &[Slide]) -> u32 {
+    slides.windows(2).fold(0, |score, slide_pair| {
+        calculate_score(&slide_pair[0].tags, &slide_pair[1].tags) + score
+    })
+}
@@ -0,0 +1,540 @@
+//! Creation of slides from pictures and their arrangement into a slideshow.
+
+use crate::tags::{index_by_tag, TagSets};
+use crate::{
+    vocabulary_size, Budget, Objective, Orientation, PairingStrategy, Picture, Rng, TagSet,
+};
+use rayon::prelude::*;
+use std::cmp;
+use std::collections::VecDeque;
+
+const PROGRESS_REPORT_INTERVAL: usize = 10000;
+//Number of candidates evaluated in parallel before checking whether a better one can remain
+const CANDIDATE_BATCH_SIZE: usize = 1024;
+
+/// A slide holding either one horizontal picture or two vertical ones.
+#[derive(Debug)]
+pub struct Slide {
+    pub picture_id: u32,
+    /// The second picture of a vertical slide
+    pub second_picture_id: Option<u32>,
+    /// Sorted union of the tags of the pictures on the slide
+    pub tags: Vec<u32>,
+}
+
+/// Put every horizontal picture on its own slide and pair up the vertical ones with the given
+/// strategy, which draws its random choices from `rng`. With an odd number of vertical pictures
+/// one of them is left out.
+pub fn create_slides(
+    pictures: &[Picture],
+    pairing: &dyn PairingStrategy,
+    rng: &mut Rng,
+    budget: &Budget,
+) -> Vec<Slide> {
+    let (horizontal_pictures, vertical_pictures): (Vec<_>, Vec<_>) =
+        pictures
+            .iter()
+            .partition(|picture| match picture.orientation {
+                Orientation::Horizontal => true,
+                Orientation::Vertical => false,
+            });
+    horizontal_pictures
+        .into_iter()
+        .map(|picture| slide_from_pictures(picture, None))
+        .chain(
+            pairing
+                .pair(&vertical_pictures, rng, budget)
+                .into_iter()
+                .map(|(first, second)| {
+                    slide_from_pictures(vertical_pictures[first], Some(vertical_pictures[second]))
+                }),
+        )
+        .collect()
+}
+
+/// Slide of a horizontal picture, or of two vertical ones when `second_picture` is given.
+pub fn slide_from_pictures(picture: &Picture, second_picture: Option<&Picture>) -> Slide {
+    Slide {
+        picture_id: picture.id,
+        second_picture_id: second_picture.map(|picture| picture.id),
+        tags: match second_picture {
+            Some(second_picture) => merge_tags(&picture.tags, &second_picture.tags),
+            None => picture.tags.clone(),
+        },
+    }
+}
+
+/// Sorted union of two sorted tag lists, so tags shared by both pictures of a slide are counted once.
+pub fn merge_tags(left_tags: &[u32], right_tags: &[u32]) -> Vec<u32> {
+    let mut tags = Vec::with_capacity(left_tags.len() + right_tags.len());
+    let (mut left_index, mut right_index) = (0, 0);
+    while left_index < left_tags.len() && right_index < right_tags.len() {
+        let (left_tag, right_tag) = (left_tags[left_index], right_tags[right_index]);
+        tags.push(cmp::min(left_tag, right_tag));
+        if left_tag <= right_tag {
+            left_index += 1;
+        }
+        if right_tag <= left_tag {
+            right_index += 1;
+        }
+    }
+    tags.extend_from_slice(&left_tags[left_index..]);
+    tags.extend_from_slice(&right_tags[right_index..]);
+    tags
+}
+
+/// Number of slides with each tag count, indexed by tag count.
+pub fn tag_count_histogram(slides: &[Slide]) -> Vec<usize> {
+    let mut histogram = Vec::new();
+    for slide in slides.iter() {
+        if histogram.len() <= slide.tags.len() {
+            histogram.resize(slide.tags.len() + 1, 0);
+        }
+        histogram[slide.tags.len()] += 1;
+    }
+    histogram
+}
+
+/// Which ends of the slideshow the greedy arrangement grows from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainGrowth {
+    /// Always follow the last slide
+    LastSlide,
+    /// Add a slide before the first one or after the last one, whichever costs less
+    BothEnds,
+}
+
+/// How the greedy arrangement chooses the next slide.
+#[derive(Debug, Clone, Copy)]
+pub struct ArrangementOptions {
+    pub growth: ChainGrowth,
+    /// Cost of the transitions the next slide is chosen by
+    pub objective: Objective,
+    /// How many of the cheapest slides are compared again including the cheapest transition that
+    /// could follow them, none when zero
+    pub lookahead: usize,
+}
+
+impl Default for ArrangementOptions {
+    fn default() -> Self {
+        ArrangementOptions {
+            growth: ChainGrowth::LastSlide,
+            objective: Objective::Waste,
+            lookahead: 0,
+        }
+    }
+}
+
+/// Order the slides greedily, always adding the slide with the cheapest transition under the
+/// objective next to an end of the slideshow, as set by `options`.
+///
+/// The first slide and the order in which equally cheap slides are preferred are drawn from
+/// `rng`, so the arrangement only depends on it and not on the number of threads. Progress is
+/// reported under `name`. Once the budget is exhausted the remaining slides are appended in that
+/// order of preference. Transitions are scored with the `TagRepresentation` suited to the
+/// vocabulary of the slides.
+pub fn arrange_slides(
+    slides: Vec<Slide>,
+    name: &str,
+    options: &ArrangementOptions,
+    rng: &mut Rng,
+    budget: &Budget,
+) -> Vec<Slide> {
+    if slides.is_empty() {
+        return slides;
+    }
+    //Ties go to the slide that comes first, so shuffling draws the order of preference
+    let mut slides = slides;
+    rng.shuffle(&mut slides);
+    let arrangement = match TagSets::new(&slides, vocabulary_size(&slides)) {
+        TagSets::SortedLists(tag_sets) => {
+            arrange_greedily(&slides, &tag_sets, name, options, rng, budget)
+        }
+        TagSets::Bitsets(tag_sets) => {
+            arrange_greedily(&slides, &tag_sets, name, options, rng, budget)
+        }
+    };
+    reorder_slides(slides, arrangement)
+}
+
+//The slides in the given order, which must hold the index of every slide once
+pub(crate) fn reorder_slides(slides: Vec<Slide>, order: Vec<usize>) -> Vec<Slide> {
+    let mut slides: Vec<_> = slides.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .map(|index| slides[index].take().expect("Every slide is placed once"))
+        .collect()
+}
+
+//Slides worth considering next to a given one among those not arranged yet
+struct CandidateFinder {
+    //Only slides sharing a tag with the current one can score with it, so those are the candidates
+    slides_by_tag: Vec<Vec<u32>>,
+    //A slide sharing no tags scores nothing and wastes all of them, so among those the one with
+    //the fewest tags is always the best and it is enough to add the smallest remaining slide to
+    //the candidates
+    slides_by_tag_count: Vec<usize>,
+    smallest_remaining: usize,
+    //Search in which a slide was last added to the candidates, to add each one only once
+    candidate_search: Vec<usize>,
+    search: usize,
+    candidates: Vec<usize>,
+}
+
+impl CandidateFinder {
+    fn new(slides: &[Slide]) -> Self {
+        let slides_by_tag = index_by_tag(slides.iter().map(|slide| slide.tags.as_slice()));
+        let mut slides_by_tag_count: Vec<_> = (0..slides.len()).collect();
+        slides_by_tag_count.sort_by_key(|&index| slides[index].tags.len());
+        CandidateFinder {
+            slides_by_tag,
+            slides_by_tag_count,
+            smallest_remaining: 0,
+            candidate_search: vec![usize::MAX; slides.len()],
+            search: 0,
+            candidates: Vec::new(),
+        }
+    }
+
+    //Candidates to follow the given slide other than itself in their order, which needn't be
+    //arranged already so that the slides following a candidate can be looked ahead at. At least
+    //one remains unless every other slide is arranged
+    fn candidates(&mut self, slides: &[Slide], slide_index: usize, arranged: &[bool]) -> &[usize] {
+        self.search += 1;
+        self.candidate_search[slide_index] = self.search;
+        self.candidates.clear();
+        for &tag in slides[slide_index].tags.iter() {
+            let tag_slides = &mut self.slides_by_tag[tag as usize];
+            tag_slides.retain(|&index| !arranged[index as usize]);
+            for &index in tag_slides.iter() {
+                if self.candidate_search[index as usize] != self.search {
+                    self.candidate_search[index as usize] = self.search;
+                    self.candidates.push(index as usize);
+                }
+            }
+        }
+        while self.smallest_remaining < slides.len()
+            && arranged[self.slides_by_tag_count[self.smallest_remaining]]
+        {
+            self.smallest_remaining += 1;
+        }
+        let smallest_slide_index = self.slides_by_tag_count[self.smallest_remaining..]
+            .iter()
+            .cloned()
+            .find(|&index| !arranged[index] && index != slide_index);
+        if let Some(smallest_slide_index) = smallest_slide_index {
+            if self.candidate_search[smallest_slide_index] != self.search {
+                self.candidates.push(smallest_slide_index);
+            }
+        }
+        //The slides of each tag are already in order, which the stable sort takes advantage of
+        self.candidates.sort();
+        &self.candidates
+    }
+}
+
+//State of the greedy arrangement, scoring transitions with tag_sets[index] for slides[index]
+struct Greedy<'a, T> {
+    slides: &'a [Slide],
+    tag_sets: &'a [T],
+    options: ArrangementOptions,
+    candidate_finder: CandidateFinder,
+    arranged: Vec<bool>,
+}
+
+impl<'a, T: TagSet + Sync> Greedy<'a, T> {
+    //Cheapest slide to add next to the given end slide and its cost, including the cheapest
+    //transition that could follow it when looking ahead
+    fn best_next_to(&mut self, end_slide_index: usize) -> Option<(i64, usize)> {
+        let objective = self.options.objective;
+        let candidates =
+            self.candidate_finder
+                .candidates(self.slides, end_slide_index, &self.arranged);
+        if self.options.lookahead == 0 {
+            return find_cheapest(end_slide_index, candidates, self.tag_sets, objective);
+        }
+        let tag_sets = self.tag_sets;
+        let tags = &tag_sets[end_slide_index];
+        let mut cheapest: Vec<_> = candidates
+            .par_iter()
+            .map(|&index| (objective.cost(tags, &tag_sets[index]), index))
+            .collect();
+        cheapest.sort_unstable();
+        cheapest.truncate(self.options.lookahead);
+        let mut best: Option<(i64, usize)> = None;
+        for (cost, index) in cheapest {
+            let following: Vec<_> = self
+                .candidate_finder
+                .candidates(self.slides, index, &self.arranged)
+                .to_vec();
+            //Nothing can follow only when the candidate would be the last slide
+            let following_cost = find_cheapest(index, &following, self.tag_sets, objective)
+                .map_or(0, |(cost, _)| cost);
+            let candidate = (cost + following_cost, index);
+            best = Some(best.map_or(candidate, |best| cmp::min(best, candidate)));
+        }
+        best
+    }
+}
+
+//Order of the slides built by arrange_slides, scoring transitions with tag_sets[index] for
+//slides[index]
+fn arrange_greedily<T: TagSet + Sync>(
+    slides: &[Slide],
+    tag_sets: &[T],
+    name: &str,
+    options: &ArrangementOptions,
+    rng: &mut Rng,
+    budget: &Budget,
+) -> Vec<usize> {
+    let mut greedy = Greedy {
+        slides,
+        tag_sets,
+        options: *options,
+        candidate_finder: CandidateFinder::new(slides),
+        arranged: vec![false; slides.len()],
+    };
+    let mut arrangement = VecDeque::with_capacity(slides.len());
+    let first_slide_index = rng.below(slides.len());
+    greedy.arranged[first_slide_index] = true;
+    arrangement.push_back(first_slide_index);
+    //Cheapest slide to add after the last slide and before the first one with its cost. Slides
+    //only ever get arranged, so without looking ahead it stays the cheapest until either it or
+    //the end slide changes
+    let mut best_after: Option<(i64, usize)> = None;
+    let mut best_before: Option<(i64, usize)> = None;
+    loop {
+        let remaining_slides = slides.len() - arrangement.len();
+        if remaining_slides == 0 {
+            break;
+        }
+        if remaining_slides.is_multiple_of(PROGRESS_REPORT_INTERVAL) {
+            println!("Slides remaining for {}: {}", name, remaining_slides);
+        }
+        if budget.is_exhausted() {
+            println!(
+                "Stopping early for {}, appending the remaining slides",
+                name
+            );
+            arrangement.extend((0..slides.len()).filter(|&index| !greedy.arranged[index]));
+            break;
+        }
+        let mut best_next_to = |best: Option<(i64, usize)>, end_slide_index: usize| match best {
+            Some((_, index)) if !greedy.arranged[index] && options.lookahead == 0 => best,
+            _ => greedy.best_next_to(end_slide_index),
+        };
+        let last_slide_index = *arrangement.back().expect("A slide is arranged");
+        best_after = best_next_to(best_after, last_slide_index);
+        let after = best_after.expect("There are slides remaining");
+        let before = match options.growth {
+            ChainGrowth::LastSlide => None,
+            ChainGrowth::BothEnds => {
+                let first_slide_index = *arrangement.front().expect("A slide is arranged");
+                best_before = best_next_to(best_before, first_slide_index);
+                best_before.filter(|&(cost, _)| cost < after.0)
+            }
+        };
+        let slide_index = match before {
+            Some((_, index)) => {
+                arrangement.push_front(index);
+                best_before = None;
+                index
+            }
+            None => {
+                arrangement.push_back(after.1);
+                best_after = None;
+                after.1
+            }
+        };
+        greedy.arranged[slide_index] = true;
+    }
+    arrangement.into_iter().collect()
+}
+
+//Candidate with the cheapest transition from the given slide and its cost, with ties going to
+//the one that comes first. The candidates must be in order and are evaluated in batches,
+//skipping those whose tag count doesn't allow beating the best found so far and stopping once no
+//remaining candidate's tag count allows it, as an equally cheap one would come later and lose
+fn find_cheapest<T: TagSet + Sync>(
+    slide_index: usize,
+    candidates: &[usize],
+    tag_sets: &[T],
+    objective: Objective,
+) -> Option<(i64, usize)> {
+    let tags = &tag_sets[slide_index];
+    let lower_bound =
+        |index: usize| objective.cost_lower_bound(tags.tag_count(), tag_sets[index].tag_count());
+    //Lowest cost the candidates from each batch on could reach
+    let mut remaining_lower_bounds: Vec<_> = candidates
+        .chunks(CANDIDATE_BATCH_SIZE)
+        .map(|batch| {
+            batch
+                .iter()
+                .map(|&index| lower_bound(index))
+                .min()
+                .expect("Batches aren't empty")
+        })
+        .collect();
+    for batch in (1..remaining_lower_bounds.len()).rev() {
+        remaining_lower_bounds[batch - 1] = cmp::min(
+            remaining_lower_bounds[batch - 1],
+            remaining_lower_bounds[batch],
+        );
+    }
+    let mut best: Option<(i64, usize)> = None;
+    for (batch, remaining_lower_bound) in candidates
+        .chunks(CANDIDATE_BATCH_SIZE)
+        .zip(remaining_lower_bounds)
+    {
+        let best_cost = best.map_or(i64::MAX, |(cost, _)| cost);
+        if best_cost <= remaining_lower_bound {
+            break;
+        }
+        let batch_best = batch
+            .par_iter()
+            .filter(|&&index| lower_bound(index) < best_cost)
+            .map(|&index| (objective.cost(tags, &tag_sets[index]), index))
+            .min();
+        best = match (best, batch_best) {
+            (Some(best), Some(batch_best)) => Some(cmp::min(best, batch_best)),
+            (best, batch_best) => best.or(batch_best),
+        };
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_input, rate_slideshow, MinimalOverlap};
+    use std::collections::HashSet;
+
+    //Deterministic input where most pictures are vertical and pairs are likely to share tags
+    fn vertical_heavy_input(picture_count: usize, vocabulary: u64) -> String {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        let mut input = picture_count.to_string();
+        for _ in 0..picture_count {
+            let orientation = if next(5) == 0 { "H" } else { "V" };
+            let mut tags: Vec<_> = (0..1 + next(12)).map(|_| next(vocabulary)).collect();
+            tags.sort_unstable();
+            tags.dedup();
+            input += &format!("\n{} {}", orientation, tags.len());
+            for tag in tags {
+                input += &format!(" tag{}", tag);
+            }
+        }
+        input
+    }
+
+    //Score a slideshow straight from the definition, using the tags of the original pictures
+    fn reference_score(slides: &[Slide], pictures: &[Picture]) -> u32 {
+        let tag_set = |slide: &Slide| -> HashSet<u32> {
+            Some(slide.picture_id)
+                .into_iter()
+                .chain(slide.second_picture_id)
+                .flat_map(|id| pictures[id as usize].tags.iter().cloned())
+                .collect()
+        };
+        slides
+            .windows(2)
+            .map(|pair| {
+                let (left, right) = (tag_set(&pair[0]), tag_set(&pair[1]));
+                let common = left.intersection(&right).count();
+                cmp::min(common, cmp::min(left.len() - common, right.len() - common)) as u32
+            })
+            .sum()
+    }
+
+    #[test]
+    fn merge_tags_is_sorted_union() {
+        assert_eq!(
+            merge_tags(&[1, 3, 5, 7], &[2, 3, 7, 8]),
+            vec![1, 2, 3, 5, 7, 8]
+        );
+        assert_eq!(merge_tags(&[], &[4, 6]), vec![4, 6]);
+        assert_eq!(merge_tags(&[2, 4], &[2, 4]), vec![2, 4]);
+    }
+
+    #[test]
+    fn vertical_slides_count_shared_tags_once() {
+        let input = vertical_heavy_input(400, 30);
+        let pictures = parse_input(input.as_bytes(), true).unwrap();
+        let mut rng = Rng::new(0);
+        let slides = create_slides(&pictures, &MinimalOverlap, &mut rng, &Budget::unlimited());
+        for slide in slides.iter() {
+            assert!(slide.tags.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+        let arranged_slides = arrange_slides(
+            slides,
+            "test",
+            &ArrangementOptions {
+                growth: ChainGrowth::BothEnds,
+                ..ArrangementOptions::default()
+            },
+            &mut rng,
+            &Budget::unlimited(),
+        );
+        assert_eq!(
+            rate_slideshow(&arranged_slides),
+            reference_score(&arranged_slides, &pictures)
+        );
+    }
+
+    #[test]
+    fn arrangement_does_not_depend_on_thread_count() {
+        //Enough slides sharing tags for candidates to span several batches
+        let input = vertical_heavy_input(2000, 30);
+        let pictures = parse_input(input.as_bytes(), true).unwrap();
+        let options = [
+            (ChainGrowth::LastSlide, 0),
+            (ChainGrowth::BothEnds, 0),
+            (ChainGrowth::BothEnds, 2),
+        ]
+        .map(|(growth, lookahead)| ArrangementOptions {
+            growth,
+            lookahead,
+            ..ArrangementOptions::default()
+        });
+        let arrange = |threads: usize| -> Vec<Vec<(u32, Option<u32>)>> {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let budget = Budget::unlimited();
+                let mut rng = Rng::new(7);
+                let slides = create_slides(&pictures, &MinimalOverlap, &mut rng, &budget);
+                options
+                    .iter()
+                    .map(|options| {
+                        let mut rng = Rng::new(11);
+                        let slides = slides
+                            .iter()
+                            .map(|slide| {
+                                slide_from_pictures(
+                                    &pictures[slide.picture_id as usize],
+                                    slide.second_picture_id.map(|id| &pictures[id as usize]),
+                                )
+                            })
+                            .collect();
+                        arrange_slides(slides, "test", options, &mut rng, &budget)
+                            .iter()
+                            .map(|slide| (slide.picture_id, slide.second_picture_id))
+                            .collect()
+                    })
+                    .collect()
+            })
+        };
+        for (options, (one_thread, four_threads)) in
+            options.iter().zip(arrange(1).into_iter().zip(arrange(4)))
+        {
+            assert_eq!(one_thread, four_threads, "{:?}", options);
+        }
+    }
+}
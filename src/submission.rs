@@ -0,0 +1,203 @@
+//! Reading and writing of slideshows in the submission format.
+
+use crate::{slide_from_pictures, Orientation, Picture, Slide};
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Write a slideshow to a file in the submission format.
+pub fn write_slides(slides: &[Slide], filename: &Path) -> io::Result<()> {
+    let output = slides
+        .iter()
+        .fold(slides.len().to_string(), |output, slide| {
+            if let Some(second_picture_id) = slide.second_picture_id {
+                output + format!("\n{} {}", slide.picture_id, second_picture_id).as_str()
+            } else {
+                output + format!("\n{}", slide.picture_id).as_str()
+            }
+        });
+    fs::write(filename, output)
+}
+
+/// Reason a submission couldn't be read or isn't a valid slideshow for its input.
+#[derive(Debug)]
+pub enum SubmissionError {
+    Io(io::Error),
+    //The first line doesn't hold the number of slides
+    BadHeader { line: usize },
+    //The number of slide lines differs from the count in the header
+    CountMismatch { declared: usize, found: usize },
+    //A slide line with something other than one or two picture ids
+    BadSlide { line: usize },
+    UnknownPicture { line: usize, id: u32 },
+    RepeatedPicture { line: usize, id: u32 },
+    //A horizontal picture sharing a slide, or a vertical one alone on it
+    WrongOrientation { line: usize, id: u32 },
+}
+
+impl fmt::Display for SubmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubmissionError::Io(error) => write!(f, "couldn't read submission: {}", error),
+            SubmissionError::BadHeader { line } => {
+                write!(f, "line {}: expected the number of slides", line)
+            }
+            SubmissionError::CountMismatch { declared, found } => {
+                write!(f, "expected {} slides, found {}", declared, found)
+            }
+            SubmissionError::BadSlide { line } => {
+                write!(f, "line {}: expected one or two picture ids", line)
+            }
+            SubmissionError::UnknownPicture { line, id } => {
+                write!(f, "line {}: there is no picture {}", line, id)
+            }
+            SubmissionError::RepeatedPicture { line, id } => {
+                write!(f, "line {}: picture {} is already used", line, id)
+            }
+            SubmissionError::WrongOrientation { line, id } => write!(
+                f,
+                "line {}: picture {} has the wrong orientation for this slide",
+                line, id
+            ),
+        }
+    }
+}
+
+impl error::Error for SubmissionError {}
+
+impl From<io::Error> for SubmissionError {
+    fn from(error: io::Error) -> Self {
+        SubmissionError::Io(error)
+    }
+}
+
+/// Picture ids of a slide in a submission, with the line it is on for diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmissionEntry {
+    pub line: usize,
+    pub picture_id: u32,
+    pub second_picture_id: Option<u32>,
+}
+
+/// Read the picture ids of each slide in a file produced by `write_slides`.
+pub fn parse_submission(mut reader: impl Read) -> Result<Vec<SubmissionEntry>, SubmissionError> {
+    let mut file = String::new();
+    reader.read_to_string(&mut file)?;
+    let mut lines = file.lines().zip(1..);
+    let declared_slides: usize = lines
+        .next()
+        .and_then(|(header, _)| header.trim().parse().ok())
+        .ok_or(SubmissionError::BadHeader { line: 1 })?;
+    //The header can't be trusted with the allocation, there are at most as many slides as lines
+    let line_count = file.lines().count();
+    let mut entries = Vec::with_capacity(cmp::min(declared_slides, line_count - 1));
+    for (line, line_number) in lines.filter(|(line, _)| !line.trim().is_empty()) {
+        let ids: Result<Vec<u32>, _> = line.split_whitespace().map(str::parse).collect();
+        let (picture_id, second_picture_id) = match ids.as_ref().map(Vec::as_slice) {
+            Ok(&[picture_id]) => (picture_id, None),
+            Ok(&[picture_id, second_picture_id]) => (picture_id, Some(second_picture_id)),
+            _ => return Err(SubmissionError::BadSlide { line: line_number }),
+        };
+        entries.push(SubmissionEntry {
+            line: line_number,
+            picture_id,
+            second_picture_id,
+        });
+    }
+    if entries.len() != declared_slides {
+        return Err(SubmissionError::CountMismatch {
+            declared: declared_slides,
+            found: entries.len(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Check that every picture exists, is used at most once and is on a slide matching its orientation.
+pub fn validate_submission(
+    entries: &[SubmissionEntry],
+    pictures: &[Picture],
+) -> Result<(), SubmissionError> {
+    let mut used = vec![false; pictures.len()];
+    for &SubmissionEntry {
+        line,
+        picture_id,
+        second_picture_id,
+    } in entries.iter()
+    {
+        let paired = second_picture_id.is_some();
+        for id in Some(picture_id).into_iter().chain(second_picture_id) {
+            let picture = pictures
+                .get(id as usize)
+                .ok_or(SubmissionError::UnknownPicture { line, id })?;
+            if used[id as usize] {
+                return Err(SubmissionError::RepeatedPicture { line, id });
+            }
+            used[id as usize] = true;
+            let valid_orientation = match picture.orientation {
+                Orientation::Horizontal => !paired,
+                Orientation::Vertical => paired,
+            };
+            if !valid_orientation {
+                return Err(SubmissionError::WrongOrientation { line, id });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read a file produced by `write_slides` back into slides of the given pictures.
+pub fn read_slides(reader: impl Read, pictures: &[Picture]) -> Result<Vec<Slide>, SubmissionError> {
+    let entries = parse_submission(reader)?;
+    validate_submission(&entries, pictures)?;
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            slide_from_pictures(
+                &pictures[entry.picture_id as usize],
+                entry.second_picture_id.map(|id| &pictures[id as usize]),
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_input;
+
+    #[test]
+    fn invalid_submissions_report_their_line() {
+        let pictures = parse_input(&b"3\nH 1 a\nV 1 b\nV 1 c\n"[..], true).unwrap();
+        let cases: &[(&str, &str)] = &[
+            ("x\n0", "BadHeader { line: 1 }"),
+            ("2\n0", "CountMismatch { declared: 2, found: 1 }"),
+            ("1\n0 1 2", "BadSlide { line: 2 }"),
+            ("2\n0\n1 3", "UnknownPicture { line: 3, id: 3 }"),
+            ("2\n\n\n0\n0", "RepeatedPicture { line: 5, id: 0 }"),
+            ("1\n0 1", "WrongOrientation { line: 2, id: 0 }"),
+            ("2\n0\n\n1", "WrongOrientation { line: 4, id: 1 }"),
+        ];
+        for &(submission, expected) in cases.iter() {
+            match read_slides(submission.as_bytes(), &pictures) {
+                Ok(_) => panic!("{:?} should be rejected with {}", submission, expected),
+                Err(error) => assert_eq!(format!("{:?}", error), expected, "{:?}", submission),
+            }
+        }
+    }
+
+    #[test]
+    fn valid_submissions_are_read() {
+        let pictures = parse_input(&b"3\nH 1 a\nV 1 b\nV 1 c\n"[..], true).unwrap();
+        let slides = read_slides(&b"2\n1 2\n\n0\n"[..], &pictures).unwrap();
+        let ids: Vec<_> = slides
+            .iter()
+            .map(|slide| (slide.picture_id, slide.second_picture_id))
+            .collect();
+        assert_eq!(ids, vec![(1, Some(2)), (0, None)]);
+        assert_eq!(slides[0].tags.len(), 2);
+    }
+}
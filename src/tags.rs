@@ -0,0 +1,215 @@
+//! Representations of the tags of a slide that transitions can be scored from.
+
+use crate::{calculate_common_tags, Slide};
+
+//Largest vocabulary for which slides are arranged with bitsets, which then take at most 512 bytes
+const BITSET_MAX_VOCABULARY: usize = 4096;
+
+/// Tags of a slide, stored in a way the number of tags shared with another slide can be counted.
+pub trait TagSet {
+    fn tag_count(&self) -> usize;
+
+    fn common_tags(&self, other: &Self) -> u32;
+}
+
+/// A sorted list of tags, the representation `Slide` itself uses.
+impl TagSet for [u32] {
+    fn tag_count(&self) -> usize {
+        self.len()
+    }
+
+    fn common_tags(&self, other: &Self) -> u32 {
+        calculate_common_tags(self, other)
+    }
+}
+
+impl TagSet for Vec<u32> {
+    fn tag_count(&self) -> usize {
+        self.len()
+    }
+
+    fn common_tags(&self, other: &Self) -> u32 {
+        calculate_common_tags(self, other)
+    }
+}
+
+impl<T: TagSet + ?Sized> TagSet for &T {
+    fn tag_count(&self) -> usize {
+        (**self).tag_count()
+    }
+
+    fn common_tags(&self, other: &Self) -> u32 {
+        (**self).common_tags(*other)
+    }
+}
+
+/// Tags as one bit per tag of the vocabulary, so that common tags are counted with a popcount.
+///
+/// Only worth it for small vocabularies, where the bitset is about as long as a tag list.
+#[derive(Debug, Clone)]
+pub struct TagBitset {
+    words: Box<[u64]>,
+    tag_count: u32,
+}
+
+impl TagBitset {
+    /// Bitset of sorted, distinct tags, all smaller than `vocabulary_size`.
+    pub fn new(tags: &[u32], vocabulary_size: usize) -> Self {
+        let mut words = vec![0u64; vocabulary_size.div_ceil(64)];
+        for &tag in tags.iter() {
+            words[tag as usize / 64] |= 1 << (tag % 64);
+        }
+        TagBitset {
+            words: words.into_boxed_slice(),
+            tag_count: tags.len() as u32,
+        }
+    }
+}
+
+impl TagSet for TagBitset {
+    fn tag_count(&self) -> usize {
+        self.tag_count as usize
+    }
+
+    fn common_tags(&self, other: &Self) -> u32 {
+        self.words
+            .iter()
+            .zip(other.words.iter())
+            .map(|(left, right)| (left & right).count_ones())
+            .sum()
+    }
+}
+
+/// Which `TagSet` the slides of a dataset are arranged with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagRepresentation {
+    SortedList,
+    Bitset,
+}
+
+impl TagRepresentation {
+    /// Bitsets for vocabularies small enough that they are faster to intersect than tag lists.
+    pub fn for_vocabulary(vocabulary_size: usize) -> Self {
+        if vocabulary_size <= BITSET_MAX_VOCABULARY {
+            TagRepresentation::Bitset
+        } else {
+            TagRepresentation::SortedList
+        }
+    }
+}
+
+//Tag sets of slides in the representation suited to their vocabulary, so that code generic over
+//TagSet can be run on whichever was chosen
+pub(crate) enum TagSets<'a> {
+    SortedLists(Vec<&'a [u32]>),
+    Bitsets(Vec<TagBitset>),
+}
+
+impl<'a> TagSets<'a> {
+    pub(crate) fn new(slides: &'a [Slide], vocabulary_size: usize) -> Self {
+        match TagRepresentation::for_vocabulary(vocabulary_size) {
+            TagRepresentation::SortedList => {
+                TagSets::SortedLists(slides.iter().map(|slide| slide.tags.as_slice()).collect())
+            }
+            TagRepresentation::Bitset => TagSets::Bitsets(
+                slides
+                    .iter()
+                    .map(|slide| TagBitset::new(&slide.tags, vocabulary_size))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Number of distinct tags the slides could use: one more than the largest tag.
+pub fn vocabulary_size(slides: &[Slide]) -> usize {
+    tag_vocabulary_size(slides.iter().map(|slide| slide.tags.as_slice()))
+}
+
+//One more than the largest tag of the sorted tag lists
+pub(crate) fn tag_vocabulary_size<'a>(tag_lists: impl Iterator<Item = &'a [u32]>) -> usize {
+    tag_lists
+        .flat_map(|tags| tags.last())
+        .max()
+        .map_or(0, |&tag| tag as usize + 1)
+}
+
+//Positions of the sorted tag lists holding each tag, indexed by tag
+pub(crate) fn index_by_tag<'a>(
+    tag_lists: impl Iterator<Item = &'a [u32]> + Clone,
+) -> Vec<Vec<u32>> {
+    let mut positions_by_tag = vec![Vec::new(); tag_vocabulary_size(tag_lists.clone())];
+    for (position, tags) in tag_lists.enumerate() {
+        for &tag in tags.iter() {
+            positions_by_tag[tag as usize].push(position as u32);
+        }
+    }
+    positions_by_tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calculate_score, Objective};
+
+    //Random sorted lists of distinct tags, all smaller than vocabulary_size
+    fn random_tag_lists(seed: u64, count: usize, vocabulary_size: usize) -> Vec<Vec<u32>> {
+        let mut state = seed;
+        let mut next = move |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        (0..count)
+            .map(|_| {
+                let length = next(vocabulary_size as u64 + 1);
+                let mut tags: Vec<_> = (0..length)
+                    .map(|_| next(vocabulary_size as u64) as u32)
+                    .collect();
+                tags.sort_unstable();
+                tags.dedup();
+                tags
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bitsets_match_sorted_lists() {
+        let objectives = [
+            Objective::Waste,
+            Objective::Score,
+            Objective::Weighted {
+                score_weight: 3,
+                waste_weight: 2,
+            },
+        ];
+        for &vocabulary_size in [1, 5, 63, 64, 65, 100, 128, 300].iter() {
+            let lists = random_tag_lists(vocabulary_size as u64, 60, vocabulary_size);
+            let bitsets: Vec<_> = lists
+                .iter()
+                .map(|tags| TagBitset::new(tags, vocabulary_size))
+                .collect();
+            for (left, left_bitset) in lists.iter().zip(bitsets.iter()) {
+                assert_eq!(left_bitset.tag_count(), left.len());
+                for (right, right_bitset) in lists.iter().zip(bitsets.iter()) {
+                    let (left, right) = (left.as_slice(), right.as_slice());
+                    assert_eq!(
+                        left_bitset.common_tags(right_bitset),
+                        left.common_tags(right)
+                    );
+                    assert_eq!(
+                        calculate_score(left_bitset, right_bitset),
+                        calculate_score(left, right)
+                    );
+                    for objective in objectives.iter() {
+                        assert_eq!(
+                            objective.cost(left_bitset, right_bitset),
+                            objective.cost(left, right)
+                        );
+                    }
+                }
+            }
+        }
+    }
+}